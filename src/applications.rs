@@ -0,0 +1,459 @@
+//! Whitelist applications, made with `/apply` and decided on from a review channel
+//!
+//! Applications are kept in `applications.json` in `DATA_PATH`, so the buttons on a review
+//! message keep working across restarts.
+
+use std::{collections::HashSet, io::ErrorKind, path::PathBuf, sync::atomic::Ordering};
+
+use poise::serenity_prelude::{
+    self as serenity, ButtonStyle, ChannelId, Color, ComponentInteraction, CreateActionRow,
+    CreateButton, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseFollowup,
+    CreateInteractionResponseMessage, CreateMessage, CreateQuickModal, EditInteractionResponse,
+    Mentionable, Timestamp, UserId,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Mutex, time::Duration};
+
+use crate::{audit, logs::is_player_name, server, storage, Context, Data, Error};
+
+const FILE_NAME: &str = "applications.json";
+/// The most text inputs Discord allows in a modal
+pub const MAX_QUESTIONS: usize = 5;
+/// The longest label Discord allows on a text input
+pub const MAX_QUESTION_LEN: usize = 45;
+/// How long an applicant has to fill in the form
+const FORM_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+/// Embed field values are capped at 1024 characters
+const FIELD_LIMIT: usize = 1024;
+/// Prefix of the custom ids on review buttons, followed by `approve:<id>` or `deny:<id>`
+const BUTTON_PREFIX: &str = "apply:";
+
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+enum Status {
+    Pending,
+    Approved,
+    Denied,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Application {
+    id: u64,
+    user: UserId,
+    /// The Minecraft name to whitelist
+    name: String,
+    /// Id of the server to whitelist on
+    server: String,
+    /// Each question with its answer
+    answers: Vec<(String, String)>,
+    submitted: i64,
+    status: Status,
+    /// Who decided, and when
+    #[serde(default)]
+    decision: Option<(UserId, i64)>,
+}
+
+impl Application {
+    fn review_embed(&self, server_name: &str) -> CreateEmbed {
+        let (status, color) = match self.status {
+            Status::Pending => ("Pending", Color::BLURPLE),
+            Status::Approved => ("Approved", Color::FOOYOO),
+            Status::Denied => ("Denied", Color::RED),
+        };
+        let mut embed = CreateEmbed::new()
+            .title(format!("Whitelist application #{}", self.id))
+            .fields([
+                ("Applicant", self.user.mention().to_string(), true),
+                ("Minecraft Name", self.name.clone(), true),
+                ("Server", server_name.to_owned(), true),
+            ]);
+        for (question, answer) in &self.answers {
+            let answer = if answer.is_empty() {
+                "-".to_string()
+            } else {
+                answer.chars().take(FIELD_LIMIT).collect()
+            };
+            embed = embed.field(question, answer, false);
+        }
+        let status = match self.decision {
+            Some((moderator, at)) => format!("{status} by {} <t:{at}:R>", moderator.mention()),
+            None => status.to_string(),
+        };
+        embed
+            .field("Status", status, false)
+            .timestamp(Timestamp::from_unix_timestamp(self.submitted).unwrap_or_default())
+            .color(color)
+    }
+
+    fn buttons(&self) -> Vec<CreateActionRow> {
+        if self.status != Status::Pending {
+            return Vec::new();
+        }
+        vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(format!("{BUTTON_PREFIX}approve:{}", self.id))
+                .label("Approve")
+                .style(ButtonStyle::Success),
+            CreateButton::new(format!("{BUTTON_PREFIX}deny:{}", self.id))
+                .label("Deny")
+                .style(ButtonStyle::Danger),
+        ])]
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct Applications {
+    next_id: u64,
+    applications: Vec<Application>,
+    /// Ids of applications a reviewer's decision is being carried out on
+    #[serde(skip)]
+    deciding: HashSet<u64>,
+}
+
+/// Every application ever made, saved whenever one changes
+pub struct ApplicationStore {
+    path: PathBuf,
+    state: Mutex<Applications>,
+}
+
+impl ApplicationStore {
+    pub async fn load(data_path: PathBuf) -> Result<Self, Error> {
+        let path = data_path.join(FILE_NAME);
+        let state = match tokio::fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Applications::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            path,
+            state: Mutex::new(state),
+        })
+    }
+
+    async fn save_locked(&self, state: &Applications) -> Result<(), Error> {
+        storage::write_atomic(&self.path, &serde_json::to_vec(state)?).await
+    }
+
+    async fn has_pending(&self, user: UserId) -> bool {
+        self.state
+            .lock()
+            .await
+            .applications
+            .iter()
+            .any(|app| app.user == user && app.status == Status::Pending)
+    }
+}
+
+fn already_pending() -> Error {
+    "You already have an application waiting to be reviewed".into()
+}
+
+/// Apply to be whitelisted
+#[poise::command(slash_command, guild_only)]
+pub async fn apply(
+    ctx: Context<'_>,
+    #[description = "Your Minecraft username"] minecraft_name: String,
+    #[description = "Defaults to the first server"]
+    #[autocomplete = "server::autocomplete"]
+    server: Option<String>,
+) -> Result<(), Error> {
+    let poise::Context::Application(app_ctx) = ctx else {
+        return Ok(());
+    };
+    let config = ctx.data().config.get();
+    let review_channel = config
+        .applications
+        .review_channel
+        .ok_or("Applications aren't open")?;
+    let server = config.servers.get(server.as_deref())?;
+    // Nothing could be approved without it, so don't let anyone apply
+    server.rcon()?;
+    if !is_player_name(&minecraft_name) {
+        return Err(format!("{minecraft_name} isn't a valid Minecraft username").into());
+    }
+    if ctx.data().applications.has_pending(ctx.author().id).await {
+        return Err(already_pending());
+    }
+
+    let questions = &config.applications.questions;
+    if questions.is_empty() {
+        let id = submit(
+            ctx.serenity_context(),
+            ctx.data(),
+            review_channel,
+            ctx.author().id,
+            minecraft_name,
+            server,
+            Vec::new(),
+        )
+        .await?;
+        ctx.send(
+            poise::CreateReply::default()
+                .content(submitted_message(id))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let modal = questions
+        .iter()
+        .fold(
+            CreateQuickModal::new("Whitelist application"),
+            |modal, q| modal.paragraph_field(q),
+        )
+        .timeout(FORM_TIMEOUT);
+    let response = app_ctx
+        .interaction
+        .quick_modal(ctx.serenity_context(), modal)
+        .await?;
+    // The modal was the response, so anything else has to follow up
+    app_ctx
+        .has_sent_initial_response
+        .store(true, Ordering::SeqCst);
+    let Some(response) = response else {
+        return Ok(());
+    };
+
+    let answers = questions.iter().cloned().zip(response.inputs).collect();
+    let content = match submit(
+        ctx.serenity_context(),
+        ctx.data(),
+        review_channel,
+        ctx.author().id,
+        minecraft_name,
+        server,
+        answers,
+    )
+    .await
+    {
+        Ok(id) => submitted_message(id),
+        Err(err) => err.to_string(),
+    };
+    response
+        .interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+fn submitted_message(id: u64) -> String {
+    format!("Application #{id} submitted, you'll get a DM once it's been reviewed")
+}
+
+/// Record an application and post it for review, returning its id
+async fn submit(
+    ctx: &serenity::Context,
+    data: &Data,
+    review_channel: ChannelId,
+    user: UserId,
+    name: String,
+    server: &server::Server,
+    answers: Vec<(String, String)>,
+) -> Result<u64, Error> {
+    let store = &data.applications;
+    let mut state = store.state.lock().await;
+    // The form may have been open long enough for another application to go in
+    if state
+        .applications
+        .iter()
+        .any(|app| app.user == user && app.status == Status::Pending)
+    {
+        return Err(already_pending());
+    }
+
+    state.next_id += 1;
+    let application = Application {
+        id: state.next_id,
+        user,
+        name,
+        server: server.id.clone(),
+        answers,
+        submitted: Timestamp::now().unix_timestamp(),
+        status: Status::Pending,
+        decision: None,
+    };
+    review_channel
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .embed(application.review_embed(&server.name))
+                .components(application.buttons()),
+        )
+        .await?;
+
+    log::info!(
+        "{} applied to be whitelisted on {} as {}",
+        user,
+        server.id,
+        application.name
+    );
+
+    let id = application.id;
+    state.applications.push(application);
+    store.save_locked(&state).await?;
+    Ok(id)
+}
+
+/// Handle a click on a review button
+pub async fn on_component(
+    ctx: &serenity::Context,
+    data: &Data,
+    interaction: &ComponentInteraction,
+) -> Result<(), Error> {
+    let Some(button) = interaction.data.custom_id.strip_prefix(BUTTON_PREFIX) else {
+        return Ok(());
+    };
+    let Some((approve, id)) = button
+        .split_once(':')
+        .and_then(|(action, id)| Some((action == "approve", id.parse::<u64>().ok()?)))
+    else {
+        return Ok(());
+    };
+
+    let reply = |content: String| {
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .ephemeral(true),
+        )
+    };
+
+    let config = data.config.get();
+    let roles = &config.permissions.review_roles;
+    let allowed = roles.is_empty()
+        || interaction
+            .member
+            .as_ref()
+            .is_some_and(|member| member.roles.iter().any(|role| roles.contains(role)));
+    if !allowed {
+        let content = "You don't have a role that can review applications".to_string();
+        interaction.create_response(ctx, reply(content)).await?;
+        return Ok(());
+    }
+
+    // Whitelisting can take a while, so answer the click before doing anything slow
+    interaction
+        .create_response(ctx, CreateInteractionResponse::Acknowledge)
+        .await?;
+    let follow_up = |content: String| async move {
+        let followup = CreateInteractionResponseFollowup::new()
+            .content(content)
+            .ephemeral(true);
+        interaction.create_followup(ctx, followup).await.map(drop)
+    };
+
+    let taken = {
+        let mut state = data.applications.state.lock().await;
+        match state.applications.iter().find(|app| app.id == id) {
+            None => Err(format!("Application #{id} no longer exists")),
+            Some(app) if app.status != Status::Pending => {
+                Err(format!("Application #{id} has already been decided"))
+            }
+            Some(_) if state.deciding.contains(&id) => {
+                Err(format!("Application #{id} is already being decided"))
+            }
+            Some(app) => {
+                let taken = (app.name.clone(), app.server.clone());
+                // Nobody else can decide on it while the lock is let go for RCON
+                state.deciding.insert(id);
+                Ok(taken)
+            }
+        }
+    };
+    let (name, server_id) = match taken {
+        Ok(taken) => taken,
+        Err(content) => {
+            follow_up(content).await?;
+            return Ok(());
+        }
+    };
+
+    let result = match config.servers.get(Some(&server_id)) {
+        Ok(server) if approve => match server.rcon() {
+            Ok(rcon) => rcon
+                .send_command(&format!("whitelist add {name}"))
+                .await
+                .map(|_| server)
+                .map_err(|err| format!("Unable to whitelist {name}: {err}").into()),
+            Err(err) => Err(err),
+        },
+        result => result,
+    };
+    let server = match result {
+        Ok(server) => server,
+        Err(err) => {
+            data.applications.state.lock().await.deciding.remove(&id);
+            follow_up(err.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    let moderator = interaction.user.id;
+    let (update, user, details) = {
+        let mut state = data.applications.state.lock().await;
+        state.deciding.remove(&id);
+        let application = state
+            .applications
+            .iter_mut()
+            .find(|app| app.id == id)
+            .ok_or("The application disappeared while it was being decided")?;
+        application.status = if approve {
+            Status::Approved
+        } else {
+            Status::Denied
+        };
+        application.decision = Some((moderator, Timestamp::now().unix_timestamp()));
+        let update = EditInteractionResponse::new()
+            .embed(application.review_embed(&server.name))
+            .components(application.buttons());
+        let details = serde_json::json!({
+            "application": application.id,
+            "applicant": application.user,
+            "player": application.name,
+            "server": application.server,
+        });
+        let user = application.user;
+        data.applications.save_locked(&state).await?;
+        (update, user, details)
+    };
+
+    let action = if approve {
+        "application.approve"
+    } else {
+        "application.deny"
+    };
+    audit::record(&data.data_path, moderator, action, details).await?;
+
+    interaction.edit_response(ctx, update).await?;
+
+    let dm = if approve {
+        format!(
+            "Your application to play on {} as {name} was approved, welcome!",
+            server.name
+        )
+    } else {
+        format!(
+            "Your application to play on {} as {name} was denied",
+            server.name
+        )
+    };
+    // Plenty of people don't accept DMs, which is no reason to undo the decision
+    let sent = match user.create_dm_channel(ctx).await {
+        Ok(channel) => channel
+            .send_message(ctx, CreateMessage::new().content(dm))
+            .await
+            .map(drop),
+        Err(err) => Err(err),
+    };
+    if let Err(err) = sent {
+        log::warn!("Unable to DM {user} about their application: {err}");
+    }
+
+    Ok(())
+}
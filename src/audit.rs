@@ -0,0 +1,30 @@
+//! Who did what, appended to `audit.jsonl` in `DATA_PATH` one JSON object per line
+
+use std::path::Path;
+
+use poise::serenity_prelude::{json, Timestamp, UserId};
+
+use crate::{storage, Error};
+
+const FILE_NAME: &str = "audit.jsonl";
+
+/// Record `moderator` taking `action`, along with whatever `details` say about it
+pub async fn record(
+    data_path: &Path,
+    moderator: UserId,
+    action: &str,
+    details: json::Value,
+) -> Result<(), Error> {
+    let mut entry = json::json!({
+        "time": Timestamp::now().unix_timestamp(),
+        "moderator": moderator,
+        "action": action,
+    });
+    if let (Some(entry), json::Value::Object(details)) = (entry.as_object_mut(), details) {
+        entry.extend(details);
+    }
+
+    log::info!("Audit: {entry}");
+
+    storage::append_line(&data_path.join(FILE_NAME), &entry.to_string()).await
+}
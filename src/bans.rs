@@ -0,0 +1,219 @@
+//! Temporary bans, kept in `bans.json` in `DATA_PATH` and lifted once they run out
+//!
+//! Bans that ran out while the bot was down are lifted as soon as it starts again.
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use poise::serenity_prelude::{Timestamp, UserId};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{Mutex, Notify},
+    time::{self, Duration},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{config::ConfigHandle, rcon, storage, Error};
+
+const FILE_NAME: &str = "bans.json";
+/// How long to wait before trying to lift a ban again, when the server couldn't be reached
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TempBan {
+    /// Id of the server the ban is on
+    pub server: String,
+    /// A player name, or an IP address if `ip` is set
+    pub target: String,
+    pub ip: bool,
+    /// When to lift the ban
+    pub until: i64,
+    pub moderator: UserId,
+    pub reason: Option<String>,
+}
+
+impl TempBan {
+    fn is(&self, server: &str, target: &str, ip: bool) -> bool {
+        self.server == server && self.target.eq_ignore_ascii_case(target) && self.ip == ip
+    }
+
+    fn pardon_command(&self) -> String {
+        let command = if self.ip { "pardon-ip" } else { "pardon" };
+        format!("{command} {}", self.target)
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct Bans {
+    bans: Vec<TempBan>,
+}
+
+pub struct BanStore {
+    path: PathBuf,
+    bans: Mutex<Bans>,
+    /// Woken when a ban is added, which may run out before the one being waited for
+    added: Notify,
+}
+
+impl BanStore {
+    pub async fn load(data_path: &Path) -> Result<Self, Error> {
+        let path = data_path.join(FILE_NAME);
+        let bans = match tokio::fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Bans::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            path,
+            bans: Mutex::new(bans),
+            added: Notify::new(),
+        })
+    }
+
+    async fn save_locked(&self, bans: &Bans) -> Result<(), Error> {
+        storage::write_atomic(&self.path, &serde_json::to_vec(bans)?).await
+    }
+
+    /// Lift `ban` when it runs out, replacing any other temporary ban on the same target
+    pub async fn add(&self, ban: TempBan) -> Result<(), Error> {
+        let mut bans = self.bans.lock().await;
+        bans.bans
+            .retain(|other| !other.is(&ban.server, &ban.target, ban.ip));
+        bans.bans.push(ban);
+        self.save_locked(&bans).await?;
+        self.added.notify_one();
+        Ok(())
+    }
+
+    /// Move the end of the temporary ban on `target` to `until`, returning whether there was one
+    pub async fn renew(
+        &self,
+        server: &str,
+        target: &str,
+        ip: bool,
+        until: i64,
+    ) -> Result<bool, Error> {
+        let mut bans = self.bans.lock().await;
+        let Some(ban) = bans.bans.iter_mut().find(|ban| ban.is(server, target, ip)) else {
+            return Ok(false);
+        };
+        ban.until = until;
+        self.save_locked(&bans).await?;
+        self.added.notify_one();
+        Ok(true)
+    }
+
+    /// Forget the temporary ban on `target`, returning whether there was one
+    pub async fn remove(&self, server: &str, target: &str, ip: bool) -> Result<bool, Error> {
+        let mut bans = self.bans.lock().await;
+        let count = bans.bans.len();
+        bans.bans.retain(|ban| !ban.is(server, target, ip));
+        let removed = bans.bans.len() != count;
+        if removed {
+            self.save_locked(&bans).await?;
+        }
+        Ok(removed)
+    }
+
+    /// The temporary bans on `server`, soonest to run out first
+    pub async fn list(&self, server: &str) -> Vec<TempBan> {
+        let bans = self.bans.lock().await;
+        let mut list: Vec<TempBan> = bans
+            .bans
+            .iter()
+            .filter(|ban| ban.server == server)
+            .cloned()
+            .collect();
+        list.sort_by_key(|ban| ban.until);
+        list
+    }
+
+    /// Lift bans as they run out, until `token` is cancelled
+    pub async fn run(&self, config: Arc<ConfigHandle>, token: CancellationToken) {
+        loop {
+            let wait = self.lift_expired(&config).await;
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = self.added.notified() => (),
+                _ = async {
+                    match wait {
+                        Some(wait) => time::sleep(wait).await,
+                        None => std::future::pending().await,
+                    }
+                } => (),
+            }
+        }
+    }
+
+    /// Lift every ban that has run out, returning how long until the next one does
+    async fn lift_expired(&self, config: &ConfigHandle) -> Option<Duration> {
+        let now = Timestamp::now().unix_timestamp();
+        let expired: Vec<TempBan> = {
+            let bans = self.bans.lock().await;
+            bans.bans
+                .iter()
+                .filter(|ban| ban.until <= now)
+                .cloned()
+                .collect()
+        };
+
+        // Commands can take a while, so don't hold the lock while they run
+        let config = config.get();
+        let mut lifted = Vec::new();
+        let mut failed = false;
+        for ban in expired {
+            let Ok(server) = config.servers.get(Some(&ban.server)) else {
+                log::warn!(
+                    "Forgetting the ban on {} since {} is no longer configured",
+                    ban.target,
+                    ban.server
+                );
+                lifted.push(ban);
+                continue;
+            };
+            match rcon::run_command(server, &ban.pardon_command()).await {
+                Ok(_) => {
+                    log::info!("Lifted the ban on {} from {}", ban.target, ban.server);
+                    lifted.push(ban);
+                }
+                Err(err) => {
+                    log::warn!(
+                        "Unable to lift the ban on {} from {}: {err}",
+                        ban.target,
+                        ban.server
+                    );
+                    failed = true;
+                }
+            }
+        }
+
+        let mut bans = self.bans.lock().await;
+        if !lifted.is_empty() {
+            // A ban renewed in the meantime has a new end, and stays
+            bans.bans.retain(|ban| {
+                !lifted.iter().any(|lifted| {
+                    lifted.is(&ban.server, &ban.target, ban.ip) && lifted.until == ban.until
+                })
+            });
+            if let Err(err) = self.save_locked(&bans).await {
+                log::error!("Unable to save {FILE_NAME}: {err}");
+            }
+        }
+
+        let next = bans
+            .bans
+            .iter()
+            .map(|ban| ban.until)
+            .filter(|until| *until > now)
+            .min()
+            .map(|until| Duration::from_secs((until - now) as u64));
+        if failed {
+            Some(next.map_or(RETRY_DELAY, |next| next.min(RETRY_DELAY)))
+        } else {
+            next
+        }
+    }
+}
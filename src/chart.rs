@@ -0,0 +1,276 @@
+//! Renders player counts over time as a PNG line chart, without any external service
+
+use crate::{history::Sample, Error};
+
+const WIDTH: usize = 640;
+const HEIGHT: usize = 240;
+const LEFT: usize = 44;
+const RIGHT: usize = 12;
+const TOP: usize = 12;
+const BOTTOM: usize = 24;
+/// Glyphs are 3x5 pixels, drawn at this scale
+const SCALE: usize = 2;
+const MAX_TICKS: u64 = 5;
+/// Counts come from the server, which can claim anything, so they're charted up to this
+const MAX_ONLINE: u64 = 1_000_000;
+
+type Rgb = [u8; 3];
+
+// Discord's dark theme, so the chart blends into the embed
+const BACKGROUND: Rgb = [0x2B, 0x2D, 0x31];
+const GRID: Rgb = [0x3F, 0x41, 0x47];
+const LABEL: Rgb = [0xB5, 0xBA, 0xC1];
+const LINE: Rgb = [0x57, 0xF2, 0x87];
+const FILL: Rgb = [0x31, 0x5C, 0x43];
+
+/// A line chart of how many players were online between `start` and `end`
+///
+/// The line is broken wherever the server was offline, or wherever consecutive samples are
+/// more than `max_gap` seconds apart because nothing was recording.
+pub fn players(samples: &[Sample], start: i64, end: i64, max_gap: i64) -> Result<Vec<u8>, Error> {
+    let mut canvas = Canvas::new();
+    let (plot_w, plot_h) = (WIDTH - LEFT - RIGHT, HEIGHT - TOP - BOTTOM);
+    let span = (end - start).max(1);
+
+    let peak = samples
+        .iter()
+        .filter_map(|s| s.online)
+        .max()
+        .unwrap_or(0)
+        .min(MAX_ONLINE);
+    let step = tick_step(peak);
+    let top = (peak.div_ceil(step) * step).max(step);
+    let y_of = |online: u64| TOP + plot_h - (online.min(top) * plot_h as u64 / top) as usize;
+
+    for tick in (0..=top).step_by(step as usize) {
+        let y = y_of(tick);
+        canvas.fill(LEFT, y, plot_w, 1, GRID);
+        let label = tick.to_string();
+        let x = (LEFT - 6).saturating_sub(text_width(&label));
+        canvas.text(x, y - 5 * SCALE / 2, &label, LABEL);
+    }
+
+    let (label_step, daily) = time_step(span);
+    let mut tick = start.div_euclid(label_step) * label_step + label_step;
+    while tick < end {
+        let x = LEFT + ((tick - start) * plot_w as i64 / span) as usize;
+        canvas.fill(x, TOP, 1, plot_h, GRID);
+        let label = if daily {
+            month_day(tick)
+        } else {
+            hour_minute(tick)
+        };
+        let width = text_width(&label);
+        if x >= LEFT + width / 2 && x + width / 2 + SCALE < WIDTH {
+            canvas.text(x - width / 2, HEIGHT - BOTTOM + 8, &label, LABEL);
+        }
+        tick += label_step;
+    }
+
+    // Several samples often land in the same column, and the busiest one matters most
+    let mut columns: Vec<Option<Column>> = vec![None; plot_w];
+    for sample in samples {
+        if sample.time < start || sample.time > end {
+            continue;
+        }
+        let x = ((sample.time - start) * (plot_w as i64 - 1) / span) as usize;
+        let column = columns[x].get_or_insert(Column {
+            first: sample.time,
+            last: sample.time,
+            online: None,
+        });
+        column.last = sample.time;
+        column.online = column.online.max(sample.online);
+    }
+
+    let mut prev: Option<(usize, usize, i64)> = None;
+    for (x, column) in columns.iter().enumerate() {
+        let Some(column) = column else { continue };
+        let Some(online) = column.online else {
+            prev = None;
+            continue;
+        };
+        let (x, y) = (LEFT + x, y_of(online));
+        match prev {
+            Some((px, py, last)) if column.first - last <= max_gap => {
+                canvas.segment(px, py, x, y, TOP + plot_h)
+            }
+            _ => canvas.segment(x, y, x, y, TOP + plot_h),
+        }
+        prev = Some((x, y, column.last));
+    }
+
+    canvas.encode()
+}
+
+#[derive(Clone, Copy)]
+struct Column {
+    first: i64,
+    last: i64,
+    /// The most players online in the column, if the server was ever online in it
+    online: Option<u64>,
+}
+
+/// A round step between y axis labels giving at most `MAX_TICKS` of them
+fn tick_step(peak: u64) -> u64 {
+    let mut magnitude = 1;
+    loop {
+        for step in [1, 2, 5].map(|m| m * magnitude) {
+            if peak.div_ceil(step) <= MAX_TICKS {
+                return step;
+            }
+        }
+        magnitude *= 10;
+    }
+}
+
+/// Seconds between x axis labels, and whether they should be dates rather than times
+fn time_step(span: i64) -> (i64, bool) {
+    const HOUR: i64 = 3600;
+    const DAY: i64 = 24 * HOUR;
+    let steps = [
+        (HOUR, false),
+        (3 * HOUR, false),
+        (6 * HOUR, false),
+        (12 * HOUR, false),
+        (DAY, true),
+        (2 * DAY, true),
+        (7 * DAY, true),
+        (14 * DAY, true),
+        (30 * DAY, true),
+    ];
+    steps
+        .into_iter()
+        .find(|(step, _)| span / step <= 8)
+        .unwrap_or((90 * DAY, true))
+}
+
+/// `HH:MM` in UTC
+fn hour_minute(time: i64) -> String {
+    let secs = time.rem_euclid(86400);
+    format!("{:02}:{:02}", secs / 3600, secs / 60 % 60)
+}
+
+/// `MM-DD` in UTC
+fn month_day(time: i64) -> String {
+    // Howard Hinnant's days_from_civil, in reverse
+    let days = time.div_euclid(86400) + 719468;
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    format!("{month:02}-{day:02}")
+}
+
+fn text_width(text: &str) -> usize {
+    (text.len() * 4 * SCALE).saturating_sub(SCALE)
+}
+
+/// Rows of a 3x5 glyph, most significant of the low 3 bits on the left
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        _ => [0; 5],
+    }
+}
+
+struct Canvas {
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new() -> Self {
+        Self {
+            pixels: BACKGROUND.repeat(WIDTH * HEIGHT),
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        if x < WIDTH && y < HEIGHT {
+            let i = (y * WIDTH + x) * 3;
+            self.pixels[i..i + 3].copy_from_slice(&color);
+        }
+    }
+
+    fn fill(&mut self, x: usize, y: usize, w: usize, h: usize, color: Rgb) {
+        for y in y..y + h {
+            for x in x..x + w {
+                self.set(x, y, color);
+            }
+        }
+    }
+
+    fn text(&mut self, x: usize, y: usize, text: &str, color: Rgb) {
+        for (i, c) in text.chars().enumerate() {
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..3 {
+                    if bits & (0b100 >> col) != 0 {
+                        let px = x + (i * 4 + col) * SCALE;
+                        self.fill(px, y + row * SCALE, SCALE, SCALE, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// A 2 pixel wide line from one point to another, shaded down to `base`
+    fn segment(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, base: usize) {
+        let mut prev_y = y0;
+        for x in x0..=x1 {
+            let y = if x1 == x0 {
+                y1
+            } else {
+                (y0 as i64 + (y1 as i64 - y0 as i64) * (x - x0) as i64 / (x1 - x0) as i64) as usize
+            };
+            // Start below the line so the shading never eats into it
+            self.fill(x, y + 2, 1, base.saturating_sub(y + 2), FILL);
+            let (lo, hi) = (prev_y.min(y), prev_y.max(y));
+            self.fill(x, lo.saturating_sub(1), 2, hi - lo + 2, LINE);
+            prev_y = y;
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, Error> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, WIDTH as u32, HEIGHT as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(png)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spoofed_counts_are_charted() {
+        let samples = [
+            Sample {
+                time: 0,
+                online: Some(u64::MAX),
+            },
+            Sample {
+                time: 60,
+                online: Some(123_456),
+            },
+        ];
+        assert!(players(&samples, 0, 120, 60).is_ok());
+    }
+}
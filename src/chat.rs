@@ -0,0 +1,471 @@
+//! Rendering of Minecraft text components for Discord
+
+use std::{collections::HashMap, fmt::Write, sync::OnceLock};
+
+use poise::serenity_prelude::json::Value;
+
+use crate::logs::escape_markdown;
+
+/// The 16 named colours, in legacy formatting code order
+const COLORS: [(&str, u32); 16] = [
+    ("black", 0x000000),
+    ("dark_blue", 0x0000AA),
+    ("dark_green", 0x00AA00),
+    ("dark_aqua", 0x00AAAA),
+    ("dark_red", 0xAA0000),
+    ("dark_purple", 0xAA00AA),
+    ("gold", 0xFFAA00),
+    ("gray", 0xAAAAAA),
+    ("dark_gray", 0x555555),
+    ("blue", 0x5555FF),
+    ("green", 0x55FF55),
+    ("aqua", 0x55FFFF),
+    ("red", 0xFF5555),
+    ("light_purple", 0xFF55FF),
+    ("yellow", 0xFFFF55),
+    ("white", 0xFFFFFF),
+];
+
+/// Approximately how Discord's clients draw the foreground colours an `ansi` code block supports
+const ANSI_COLORS: [(u8, u32); 8] = [
+    (30, 0x4F545C),
+    (31, 0xDC322F),
+    (32, 0x859900),
+    (33, 0xB58900),
+    (34, 0x268BD2),
+    (35, 0xD33682),
+    (36, 0x2AA198),
+    (37, 0xFFFFFF),
+];
+
+/// The bundled subset of the vanilla `en_us` language file
+pub fn lang() -> &'static HashMap<String, String> {
+    static LANG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+    LANG.get_or_init(|| {
+        serde_json::from_str(include_str!("../assets/en_us.json"))
+            .expect("Bundled language data is invalid")
+    })
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+struct Style {
+    color: Option<u32>,
+    bold: bool,
+    italic: bool,
+    underlined: bool,
+    strikethrough: bool,
+    obfuscated: bool,
+}
+
+impl Style {
+    fn inherit(mut self, component: &serde_json::Map<String, Value>) -> Self {
+        if let Some(color) = component.get("color").and_then(Value::as_str) {
+            self.color = parse_color(color).or(self.color);
+        }
+        let flag = |key: &str, current: bool| {
+            component
+                .get(key)
+                .and_then(Value::as_bool)
+                .unwrap_or(current)
+        };
+        self.bold = flag("bold", self.bold);
+        self.italic = flag("italic", self.italic);
+        self.underlined = flag("underlined", self.underlined);
+        self.strikethrough = flag("strikethrough", self.strikethrough);
+        self.obfuscated = flag("obfuscated", self.obfuscated);
+        self
+    }
+
+    fn markers(&self) -> String {
+        let mut markers = String::new();
+        if self.obfuscated {
+            markers.push_str("||");
+        }
+        if self.bold {
+            markers.push_str("**");
+        }
+        if self.italic {
+            markers.push('*');
+        }
+        if self.underlined {
+            markers.push_str("__");
+        }
+        if self.strikethrough {
+            markers.push_str("~~");
+        }
+        markers
+    }
+
+    fn ansi(&self) -> String {
+        let mut codes = vec![0];
+        if self.bold {
+            codes.push(1);
+        }
+        if self.underlined {
+            codes.push(4);
+        }
+        if let Some(color) = self.color {
+            codes.push(nearest_ansi(color));
+        }
+        format!(
+            "\x1b[{}m",
+            codes
+                .iter()
+                .map(u8::to_string)
+                .collect::<Vec<_>>()
+                .join(";")
+        )
+    }
+}
+
+fn parse_color(color: &str) -> Option<u32> {
+    if let Some(hex) = color.strip_prefix('#') {
+        u32::from_str_radix(hex, 16).ok()
+    } else {
+        COLORS
+            .iter()
+            .find(|(name, _)| *name == color)
+            .map(|(_, rgb)| *rgb)
+    }
+}
+
+fn nearest_ansi(rgb: u32) -> u8 {
+    let channels = |c: u32| {
+        [
+            (c >> 16) as i32 & 0xFF,
+            (c >> 8) as i32 & 0xFF,
+            c as i32 & 0xFF,
+        ]
+    };
+    let [r, g, b] = channels(rgb);
+    ANSI_COLORS
+        .iter()
+        .min_by_key(|(_, candidate)| {
+            let [cr, cg, cb] = channels(*candidate);
+            (r - cr).pow(2) + (g - cg).pow(2) + (b - cb).pow(2)
+        })
+        .map_or(37, |(code, _)| *code)
+}
+
+struct Span {
+    text: String,
+    style: Style,
+}
+
+/// A text component flattened into runs of identically styled text
+#[derive(Default)]
+pub struct Text {
+    spans: Vec<Span>,
+}
+
+impl Text {
+    /// Parse a JSON text component, as found in status responses and `tellraw`
+    pub fn from_json(value: &Value) -> Self {
+        let mut text = Self::default();
+        text.push_json(value, Style::default());
+        text
+    }
+
+    /// Parse a string that may contain legacy `§` formatting codes
+    pub fn from_legacy(legacy: &str) -> Self {
+        let mut text = Self::default();
+        text.push_legacy(legacy, Style::default());
+        text
+    }
+
+    fn push(&mut self, text: &str, style: Style) {
+        if text.is_empty() {
+            return;
+        }
+        match self.spans.last_mut() {
+            Some(last) if last.style == style => last.text.push_str(text),
+            _ => self.spans.push(Span {
+                text: text.to_owned(),
+                style,
+            }),
+        }
+    }
+
+    fn push_legacy(&mut self, legacy: &str, base: Style) {
+        let mut style = base;
+        let mut segments = legacy.split('§');
+        if let Some(first) = segments.next() {
+            self.push(first, style);
+        }
+        while let Some(segment) = segments.next() {
+            let mut segment = segment.chars();
+            match segment.next().map(|c| c.to_ascii_lowercase()) {
+                // Plugins spell out hex colours as `§x§R§R§G§G§B§B`
+                Some('x') if segment.as_str().is_empty() => {
+                    let mut digits = segments.clone();
+                    let mut hex = String::new();
+                    let mut rest = "";
+                    while hex.len() < 6 {
+                        let Some(digit) = digits.next() else { break };
+                        let Some(c) = digit.chars().next().filter(char::is_ascii_hexdigit) else {
+                            break;
+                        };
+                        // Only the last digit can have text after it
+                        if digit.len() > 1 && hex.len() < 5 {
+                            break;
+                        }
+                        hex.push(c);
+                        rest = &digit[1..];
+                    }
+                    if hex.len() == 6 {
+                        let color = u32::from_str_radix(&hex, 16).ok();
+                        segments = digits;
+                        style = Style {
+                            color,
+                            ..Style::default()
+                        };
+                        self.push(rest, style);
+                        continue;
+                    }
+                }
+                Some(code @ ('0'..='9' | 'a'..='f')) => {
+                    let index = code.to_digit(16).unwrap() as usize;
+                    style = Style {
+                        color: Some(COLORS[index].1),
+                        ..Style::default()
+                    }
+                }
+                Some('k') => style.obfuscated = true,
+                Some('l') => style.bold = true,
+                Some('m') => style.strikethrough = true,
+                Some('n') => style.underlined = true,
+                Some('o') => style.italic = true,
+                Some('r') => style = base,
+                _ => (),
+            }
+            self.push(segment.as_str(), style);
+        }
+    }
+
+    fn push_json(&mut self, value: &Value, parent: Style) {
+        match value {
+            Value::String(text) => self.push_legacy(text, parent),
+            Value::Number(n) => self.push(&n.to_string(), parent),
+            Value::Bool(b) => self.push(&b.to_string(), parent),
+            // The first element of an array is the parent of the rest
+            Value::Array(components) => {
+                if let Some((first, rest)) = components.split_first() {
+                    self.push_json(first, parent);
+                    let style = first
+                        .as_object()
+                        .map_or(parent, |first| parent.inherit(first));
+                    for component in rest {
+                        self.push_json(component, style);
+                    }
+                }
+            }
+            Value::Object(component) => {
+                let style = parent.inherit(component);
+
+                if let Some(text) = component.get("text") {
+                    self.push_json(text, style);
+                } else if let Some(key) = component.get("translate").and_then(Value::as_str) {
+                    let args = component
+                        .get("with")
+                        .and_then(Value::as_array)
+                        .map_or(&[][..], Vec::as_slice);
+                    let fallback = component.get("fallback").and_then(Value::as_str);
+                    self.push_translation(key, fallback, args, style);
+                } else if let Some(key) = component.get("keybind").and_then(Value::as_str) {
+                    self.push(key, style);
+                } else if let Some(selector) = component.get("selector").and_then(Value::as_str) {
+                    self.push(selector, style);
+                } else if let Some(score) = component.get("score") {
+                    let name = score["name"].as_str().unwrap_or_default();
+                    self.push(name, style);
+                }
+
+                if let Some(extra) = component.get("extra").and_then(Value::as_array) {
+                    for component in extra {
+                        self.push_json(component, style);
+                    }
+                }
+            }
+            Value::Null => (),
+        }
+    }
+
+    fn push_translation(
+        &mut self,
+        key: &str,
+        fallback: Option<&str>,
+        args: &[Value],
+        style: Style,
+    ) {
+        let Some(format) = lang().get(key).map(String::as_str).or(fallback) else {
+            // Unknown keys are shown as-is, like the client does
+            self.push(key, style);
+            return;
+        };
+
+        let mut next_arg = 0;
+        let mut rest = format;
+        while let Some(i) = rest.find('%') {
+            self.push(&rest[..i], style);
+            rest = &rest[i + 1..];
+
+            if let Some(tail) = rest.strip_prefix('%') {
+                self.push("%", style);
+                rest = tail;
+            } else if let Some(tail) = rest.strip_prefix('s') {
+                if let Some(arg) = args.get(next_arg) {
+                    self.push_json(arg, style);
+                }
+                next_arg += 1;
+                rest = tail;
+            } else if let Some((index, tail)) = rest.split_once("$s") {
+                match index.parse::<usize>() {
+                    Ok(index) => {
+                        if let Some(arg) = index.checked_sub(1).and_then(|i| args.get(i)) {
+                            self.push_json(arg, style);
+                        }
+                        rest = tail;
+                    }
+                    Err(_) => self.push("%", style),
+                }
+            } else {
+                self.push("%", style);
+            }
+        }
+        self.push(rest, style);
+    }
+
+    /// Whether any part of the text is coloured
+    pub fn has_color(&self) -> bool {
+        self.spans.iter().any(|span| span.style.color.is_some())
+    }
+
+    /// Discord markdown, keeping everything except colour
+    pub fn markdown(&self) -> String {
+        let mut out = String::new();
+        let mut prev_marked = false;
+
+        for span in &self.spans {
+            let markers = span.style.markers();
+
+            // Markers can't span lines, so style each line separately
+            for (i, line) in span.text.split('\n').enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                    prev_marked = false;
+                }
+
+                let trimmed = line.trim();
+                if markers.is_empty() || trimmed.is_empty() {
+                    out.push_str(&escape_markdown(line));
+                    prev_marked = false;
+                    continue;
+                }
+
+                let leading = &line[..line.len() - line.trim_start().len()];
+                let trailing = &line[line.trim_end().len()..];
+                if prev_marked && leading.is_empty() {
+                    // Keep adjacent formatting from merging into something else
+                    out.push('\u{200B}');
+                }
+                let closing: String = markers.chars().rev().collect();
+                let _ = write!(
+                    out,
+                    "{leading}{markers}{}{closing}{trailing}",
+                    escape_markdown(trimmed)
+                );
+                prev_marked = trailing.is_empty();
+            }
+        }
+
+        out
+    }
+
+    /// An `ansi` code block, which Discord renders with (approximate) colours
+    pub fn ansi(&self) -> String {
+        let mut out = String::from("```ansi\n");
+        for span in &self.spans {
+            out.push_str(&span.style.ansi());
+            // Nothing can close the code block early
+            out.push_str(&span.text.replace('`', "\u{2035}"));
+        }
+        out.push_str("\x1b[0m\n```");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Text;
+
+    #[test]
+    fn legacy_codes() {
+        let text = Text::from_legacy("§4Hello §lWorld§r!");
+        assert!(text.has_color());
+        assert_eq!(text.markdown(), "Hello **World**!");
+        assert_eq!(
+            text.ansi(),
+            "```ansi\n\x1b[0;31mHello \x1b[0;1;31mWorld\x1b[0m!\x1b[0m\n```"
+        );
+    }
+
+    #[test]
+    fn legacy_hex_colors() {
+        let text = Text::from_legacy("§x§F§F§5§5§5§5Red §x§0§0text");
+        assert_eq!(text.markdown(), "Red text");
+        assert!(text.ansi().starts_with("```ansi\n\x1b[0;31mRed "));
+    }
+
+    #[test]
+    fn extra_and_arrays() {
+        let text = Text::from_json(&json!({
+            "text": "A",
+            "bold": true,
+            "extra": [{ "text": "B" }, { "text": "C", "bold": false }],
+        }));
+        assert_eq!(text.markdown(), "**AB**C");
+
+        let text = Text::from_json(&json!(["", { "text": "x", "italic": true }, "y"]));
+        assert_eq!(text.markdown(), "*x*y");
+
+        let text = Text::from_json(&json!([{ "text": "x", "italic": true }, "y"]));
+        assert_eq!(text.markdown(), "*xy*");
+    }
+
+    #[test]
+    fn translations() {
+        let lang = json!({ "translate": "advancements.story.mine_stone.title" });
+        assert_eq!(Text::from_json(&lang).markdown(), "Stone Age");
+
+        let fallback = json!({
+            "translate": "not.a.key",
+            "fallback": "%2$s, then %1$s and 100%%",
+            "with": ["first", { "text": "second", "bold": true }],
+        });
+        assert_eq!(
+            Text::from_json(&fallback).markdown(),
+            "**second**, then first and 100%"
+        );
+
+        let unknown = json!({ "translate": "not.a.key", "with": ["ignored"] });
+        assert_eq!(Text::from_json(&unknown).markdown(), "not.a.key");
+    }
+
+    #[test]
+    fn markdown_escapes_and_splits_lines() {
+        let text = Text::from_json(&json!({ "text": "*a*\n b_c", "underlined": true }));
+        assert_eq!(text.markdown(), "__\\*a\\*__\n __b\\_c__");
+    }
+
+    #[test]
+    fn ansi_keeps_the_block_closed() {
+        let text = Text::from_legacy("§c```");
+        assert_eq!(
+            text.ansi(),
+            "```ansi\n\x1b[0;31m\u{2035}\u{2035}\u{2035}\x1b[0m\n```"
+        );
+    }
+}
@@ -0,0 +1,472 @@
+//! Configuration from `config.toml` in `DATA_PATH`, reloaded whenever the file changes
+//!
+//! The environment variables used before there was a config file still work, and take
+//! precedence over the file for the first server.
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode, DebounceEventResult};
+use poise::serenity_prelude::{ChannelId, GuildId, RoleId};
+use serde::Deserialize;
+use tokio::{sync::mpsc, time::Duration};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    applications,
+    server::{Server, Servers},
+    Context, Error,
+};
+
+pub const FILE_NAME: &str = "config.toml";
+const DEBOUNCE: Duration = Duration::from_millis(500);
+/// Anything more frequent risks being rate limited by Discord
+const MIN_INTERVAL: u64 = 10;
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(default)]
+    servers: Vec<ServerConfig>,
+    #[serde(default)]
+    monitors: Monitors,
+    #[serde(default)]
+    permissions: Permissions,
+    #[serde(default)]
+    applications: Applications,
+    #[serde(default)]
+    role_sync: RoleSync,
+}
+
+/// One `[[servers]]` entry
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    /// What commands refer to the server by
+    pub id: String,
+    /// Shown in embeds and autocomplete, defaults to the id
+    pub name: Option<String>,
+    #[serde(default = "default_host")]
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default = "default_bedrock_port")]
+    pub bedrock_port: u16,
+    pub query_port: Option<u16>,
+    /// RCON is unavailable without this
+    pub rcon: Option<RconConfig>,
+    /// The server's directory
+    #[serde(default = "default_path")]
+    pub path: PathBuf,
+    /// Defaults to `logs/latest.log` in the server's directory
+    pub log: Option<PathBuf>,
+    /// Where the bot keeps what it records about this server, defaults to a directory named
+    /// after the id in `DATA_PATH`
+    pub data_path: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RconConfig {
+    #[serde(default = "default_rcon_port")]
+    pub port: u16,
+    pub password: String,
+}
+
+fn default_host() -> String {
+    "localhost".into()
+}
+
+fn default_port() -> u16 {
+    25565
+}
+
+fn default_bedrock_port() -> u16 {
+    19132
+}
+
+fn default_rcon_port() -> u16 {
+    25575
+}
+
+fn default_path() -> PathBuf {
+    "/server".into()
+}
+
+/// Intervals in seconds
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Monitors {
+    status_interval: u64,
+    bedrock_interval: u64,
+    players_interval: u64,
+    /// Whether status monitors post when the server goes down and comes back
+    pub outage_alerts: bool,
+    /// Mentioned when the server goes down
+    pub outage_role: Option<RoleId>,
+}
+
+impl Default for Monitors {
+    fn default() -> Self {
+        Self {
+            status_interval: 250,
+            bedrock_interval: 250,
+            players_interval: 60,
+            outage_alerts: false,
+            outage_role: None,
+        }
+    }
+}
+
+impl Monitors {
+    pub fn status_interval(&self) -> Duration {
+        Duration::from_secs(self.status_interval)
+    }
+
+    pub fn bedrock_interval(&self) -> Duration {
+        Duration::from_secs(self.bedrock_interval)
+    }
+
+    pub fn players_interval(&self) -> Duration {
+        Duration::from_secs(self.players_interval)
+    }
+}
+
+/// Roles allowed to use each group of commands, on top of Discord's own command permissions.
+/// Empty means anyone Discord lets use the command.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Permissions {
+    pub rcon_roles: Vec<RoleId>,
+    pub monitor_roles: Vec<RoleId>,
+    /// Who can approve and deny whitelist applications, on top of seeing the review channel
+    pub review_roles: Vec<RoleId>,
+}
+
+/// Whitelist applications made with `/apply`
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Applications {
+    /// Where applications are posted for review, and without which nobody can apply
+    pub review_channel: Option<ChannelId>,
+    /// Asked in a form when applying, up to [`applications::MAX_QUESTIONS`]
+    pub questions: Vec<String>,
+}
+
+/// Whitelisting and opping whoever holds a role, going by the account they've linked
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoleSync {
+    /// The guild the roles are in
+    pub guild: Option<GuildId>,
+    /// Holding any of these gets your account whitelisted
+    pub whitelist_roles: Vec<RoleId>,
+    /// Holding any of these gets your account whitelisted and opped
+    pub op_roles: Vec<RoleId>,
+    /// Ids of the servers to keep in sync, empty for every server with RCON
+    pub servers: Vec<String>,
+    /// Seconds between syncs, on top of syncing whenever someone's roles change
+    interval: u64,
+}
+
+impl Default for RoleSync {
+    fn default() -> Self {
+        Self {
+            guild: None,
+            whitelist_roles: Vec::new(),
+            op_roles: Vec::new(),
+            servers: Vec::new(),
+            interval: 600,
+        }
+    }
+}
+
+impl RoleSync {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval)
+    }
+
+    /// The guild to sync from, if there are any roles to sync
+    pub fn guild(&self) -> Option<GuildId> {
+        self.guild
+            .filter(|_| !self.whitelist_roles.is_empty() || !self.op_roles.is_empty())
+    }
+
+    /// The servers to keep in sync
+    pub fn servers<'a>(&'a self, servers: &'a Servers) -> impl Iterator<Item = &'a Server> {
+        servers.iter().filter(|server| {
+            if self.servers.is_empty() {
+                server.rcon.is_some()
+            } else {
+                self.servers.contains(&server.id)
+            }
+        })
+    }
+}
+
+pub struct Config {
+    pub servers: Servers,
+    pub monitors: Monitors,
+    pub permissions: Permissions,
+    pub applications: Applications,
+    pub role_sync: RoleSync,
+}
+
+impl Config {
+    async fn load(data_path: &Path) -> Result<Self, Error> {
+        let path = data_path.join(FILE_NAME);
+        let mut file: File = match tokio::fs::read_to_string(&path).await {
+            Ok(text) => {
+                toml::from_str(&text).map_err(|err| format!("{}: {err}", path.display()))?
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => File::default(),
+            Err(err) => return Err(err.into()),
+        };
+
+        if file.servers.is_empty() {
+            file.servers.push(ServerConfig {
+                id: "default".into(),
+                name: Some("Minecraft Server".into()),
+                host: default_host(),
+                port: default_port(),
+                bedrock_port: default_bedrock_port(),
+                query_port: None,
+                rcon: None,
+                path: default_path(),
+                log: None,
+                // Where everything was kept before there could be more than one server
+                data_path: Some(data_path.to_owned()),
+            });
+        }
+        apply_env(&mut file.servers[0])?;
+
+        validate(&file).map_err(|err| format!("{}: {err}", path.display()))?;
+
+        let servers: Vec<Server> = file
+            .servers
+            .into_iter()
+            .map(|server| Server::from_config(server, data_path))
+            .collect();
+        for server in &servers {
+            tokio::fs::create_dir_all(&server.data_path).await?;
+        }
+
+        Ok(Self {
+            servers: Servers::new(servers),
+            monitors: file.monitors,
+            permissions: file.permissions,
+            applications: file.applications,
+            role_sync: file.role_sync,
+        })
+    }
+}
+
+/// Override the first server with whatever is set in the environment
+fn apply_env(server: &mut ServerConfig) -> Result<(), Error> {
+    let var = |key: &str| std::env::var(key).ok();
+    let port = |key: &str| -> Result<Option<u16>, Error> {
+        var(key)
+            .map(|port| {
+                port.parse()
+                    .map_err(|_| format!("{key}: invalid port {port:?}"))
+            })
+            .transpose()
+            .map_err(Into::into)
+    };
+
+    if let Some(name) = var("SERVER_NAME") {
+        server.name = Some(name);
+    }
+    if let Some(host) = var("SERVER_HOST") {
+        server.host = host;
+    }
+    if let Some(port) = port("SERVER_PORT")? {
+        server.port = port;
+    } else if let Some(port) = port("SEVER_PORT")? {
+        log::warn!("SEVER_PORT is deprecated, use SERVER_PORT");
+        server.port = port;
+    }
+    if let Some(port) = port("BEDROCK_PORT")? {
+        server.bedrock_port = port;
+    }
+    if let Some(port) = port("QUERY_PORT")? {
+        server.query_port = Some(port);
+    }
+    if let Some(path) = var("SERVER_PATH") {
+        server.path = path.into();
+    }
+    if let Some(password) = var("RCON_PASSWORD") {
+        server.rcon = Some(RconConfig {
+            port: server
+                .rcon
+                .as_ref()
+                .map_or_else(default_rcon_port, |rcon| rcon.port),
+            password,
+        });
+    }
+    if let (Some(port), Some(rcon)) = (port("RCON_PORT")?, server.rcon.as_mut()) {
+        rcon.port = port;
+    }
+
+    Ok(())
+}
+
+/// Check what deserializing can't, naming the offending key
+fn validate(file: &File) -> Result<(), String> {
+    for (i, server) in file.servers.iter().enumerate() {
+        if server.id.is_empty() {
+            return Err(format!("servers[{i}].id: must not be empty"));
+        }
+        if let Some(first) = file.servers[..i]
+            .iter()
+            .position(|other| other.id == server.id)
+        {
+            return Err(format!(
+                "servers[{i}].id: {:?} is already used by servers[{first}]",
+                server.id
+            ));
+        }
+        if server.host.is_empty() {
+            return Err(format!("servers[{i}].host: must not be empty"));
+        }
+    }
+
+    if file.applications.questions.len() > applications::MAX_QUESTIONS {
+        return Err(format!(
+            "applications.questions: at most {} are allowed",
+            applications::MAX_QUESTIONS
+        ));
+    }
+    for (i, question) in file.applications.questions.iter().enumerate() {
+        let len = question.chars().count();
+        if !(1..=applications::MAX_QUESTION_LEN).contains(&len) {
+            return Err(format!(
+                "applications.questions[{i}]: must be 1 to {} characters",
+                applications::MAX_QUESTION_LEN
+            ));
+        }
+    }
+
+    let sync = &file.role_sync;
+    if sync.guild.is_none() && (!sync.whitelist_roles.is_empty() || !sync.op_roles.is_empty()) {
+        return Err("role_sync.guild: must be set to sync roles".into());
+    }
+    for (i, id) in sync.servers.iter().enumerate() {
+        if !file.servers.iter().any(|server| &server.id == id) {
+            return Err(format!("role_sync.servers[{i}]: unknown server {id:?}"));
+        }
+    }
+
+    let intervals = [
+        ("monitors.status_interval", file.monitors.status_interval),
+        ("monitors.bedrock_interval", file.monitors.bedrock_interval),
+        ("monitors.players_interval", file.monitors.players_interval),
+        ("role_sync.interval", sync.interval),
+    ];
+    for (key, interval) in intervals {
+        if interval < MIN_INTERVAL {
+            return Err(format!("{key}: must be at least {MIN_INTERVAL} seconds"));
+        }
+    }
+
+    Ok(())
+}
+
+/// The current configuration, swapped out whenever the file changes
+pub struct ConfigHandle {
+    data_path: PathBuf,
+    current: RwLock<Arc<Config>>,
+}
+
+impl ConfigHandle {
+    pub async fn load(data_path: PathBuf) -> Result<Self, Error> {
+        let config = Config::load(&data_path).await?;
+        Ok(Self {
+            data_path,
+            current: RwLock::new(Arc::new(config)),
+        })
+    }
+
+    /// A snapshot of the configuration, which a reload won't change
+    pub fn get(&self) -> Arc<Config> {
+        self.current.read().unwrap().clone()
+    }
+
+    async fn reload(&self) -> Result<(), Error> {
+        let mut config = Config::load(&self.data_path).await?;
+        config.servers.keep_connections(&self.get().servers);
+        *self.current.write().unwrap() = Arc::new(config);
+        Ok(())
+    }
+
+    /// Reload the configuration whenever the file changes, until `token` is cancelled
+    ///
+    /// An invalid file is reported and otherwise ignored, leaving the last good configuration
+    /// in place.
+    pub async fn watch(&self, token: CancellationToken) -> Result<(), Error> {
+        let (tx, mut events) = mpsc::unbounded_channel();
+        let mut debouncer = new_debouncer(DEBOUNCE, move |res: DebounceEventResult| match res {
+            Ok(events) => {
+                if events
+                    .iter()
+                    .any(|event| event.path.file_name() == Some(FILE_NAME.as_ref()))
+                {
+                    let _ = tx.send(());
+                }
+            }
+            Err(err) => log::warn!("Error watching {FILE_NAME}: {err}"),
+        })?;
+        // Editors often replace the file rather than write to it, so watch the directory
+        debouncer
+            .watcher()
+            .watch(&self.data_path, RecursiveMode::NonRecursive)?;
+
+        loop {
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = events.recv() => (),
+            }
+
+            match self.reload().await {
+                Ok(()) => log::info!("Reloaded {FILE_NAME}"),
+                Err(err) => log::error!("Not reloading {FILE_NAME}: {err}"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether the author may use a command limited to `roles`, telling them if they can't
+async fn has_role(ctx: Context<'_>, roles: &[RoleId]) -> Result<bool, Error> {
+    if roles.is_empty() {
+        return Ok(true);
+    }
+
+    let allowed = match ctx.author_member().await {
+        Some(member) => member.roles.iter().any(|role| roles.contains(role)),
+        None => false,
+    };
+    if !allowed {
+        ctx.send(
+            poise::CreateReply::default()
+                .content("You don't have a role that can use this command")
+                .ephemeral(true),
+        )
+        .await?;
+    }
+    Ok(allowed)
+}
+
+pub async fn check_rcon(ctx: Context<'_>) -> Result<bool, Error> {
+    let config = ctx.data().config.get();
+    has_role(ctx, &config.permissions.rcon_roles).await
+}
+
+pub async fn check_monitor(ctx: Context<'_>) -> Result<bool, Error> {
+    let config = ctx.data().config.get();
+    has_role(ctx, &config.permissions.monitor_roles).await
+}
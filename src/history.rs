@@ -0,0 +1,154 @@
+//! How many players were online over time, appended to `players.csv` in the server's data
+//! directory by the status monitor
+
+use std::{io::ErrorKind, path::Path};
+
+use poise::serenity_prelude::Timestamp;
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+use crate::{chart, util::parse_duration, Context, Error};
+
+const FILE_NAME: &str = "players.csv";
+pub const CHART_FILE: &str = "players.png";
+/// How far apart samples can be before the chart assumes nothing was recording in between,
+/// in status intervals
+const GAP_INTERVALS: i64 = 3;
+const RANGES: [&str; 4] = ["6h", "24h", "7d", "30d"];
+/// The furthest back a chart goes, in seconds
+pub const MAX_RANGE: i64 = 365 * 86400;
+
+/// One poll of the server
+pub struct Sample {
+    pub time: i64,
+    /// How many players were online, or nothing if the server was offline
+    pub online: Option<u64>,
+}
+
+impl Sample {
+    pub fn now(online: Option<u64>) -> Self {
+        Self {
+            time: Timestamp::now().unix_timestamp(),
+            online,
+        }
+    }
+
+    /// `time,online`, leaving `online` empty if the server was offline
+    fn to_line(&self) -> String {
+        let online = self.online.map_or_else(String::new, |n| n.to_string());
+        format!("{},{online}\n", self.time)
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let (time, online) = line.split_once(',')?;
+        Some(Self {
+            time: time.parse().ok()?,
+            online: match online {
+                "" => None,
+                online => Some(online.parse().ok()?),
+            },
+        })
+    }
+}
+
+pub async fn record(data_path: &Path, sample: &Sample) -> Result<(), Error> {
+    // A torn line from a crash is skipped when reading, so appending is safe enough
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(data_path.join(FILE_NAME))
+        .await?;
+    file.write_all(sample.to_line().as_bytes()).await?;
+    Ok(())
+}
+
+/// Every sample taken since `since`, oldest first
+pub async fn load(data_path: &Path, since: i64) -> Result<Vec<Sample>, Error> {
+    let text = match tokio::fs::read_to_string(data_path.join(FILE_NAME)).await {
+        Ok(text) => text,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    Ok(text
+        .lines()
+        .filter_map(Sample::from_line)
+        .filter(|sample| sample.time >= since)
+        .collect())
+}
+
+/// A chart of the last `range` seconds of `samples`
+pub fn render(samples: &[Sample], range: i64, interval: i64) -> Result<Vec<u8>, Error> {
+    let end = Timestamp::now().unix_timestamp();
+    let start = end - range.min(MAX_RANGE);
+    chart::players(samples, start, end, interval * GAP_INTERVALS)
+}
+
+/// A chart of the last `range` seconds of player counts
+pub async fn chart(data_path: &Path, range: i64, interval: i64) -> Result<Vec<u8>, Error> {
+    let since = Timestamp::now().unix_timestamp() - range.min(MAX_RANGE);
+    render(&load(data_path, since).await?, range, interval)
+}
+
+async fn autocomplete_range(_ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let mut ranges: Vec<String> = RANGES
+        .iter()
+        .filter(|range| range.starts_with(partial))
+        .map(|range| range.to_string())
+        .collect();
+    // Anything parseable works, so offer what's been typed as well
+    if parse_duration(partial).is_ok() && !ranges.iter().any(|range| range == partial) {
+        ranges.insert(0, partial.to_owned());
+    }
+    ranges
+}
+
+#[poise::command(slash_command, subcommands("stats::players"), subcommand_required)]
+pub async fn stats(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+pub mod stats {
+    use poise::serenity_prelude::{CreateAttachment, CreateEmbed, CreateEmbedFooter};
+
+    use super::{autocomplete_range, chart, CHART_FILE, MAX_RANGE};
+    use crate::{server, util::parse_duration, Context, Error};
+
+    /// Chart how many players were online
+    #[poise::command(slash_command)]
+    pub async fn players(
+        ctx: Context<'_>,
+        #[description = "How far back to go, like 24h, 7d or 2w (default 24h)"]
+        #[autocomplete = "autocomplete_range"]
+        range: Option<String>,
+        #[description = "Defaults to the first server"]
+        #[autocomplete = "server::autocomplete"]
+        server: Option<String>,
+    ) -> Result<(), Error> {
+        let range_name = range.as_deref().unwrap_or("24h").trim();
+        let range = parse_duration(range_name)?;
+        if range > MAX_RANGE {
+            return Err("Charts go back a year at most".into());
+        }
+        let config = ctx.data().config.get();
+        let server = config.servers.get(server.as_deref())?;
+        let interval = config.monitors.status_interval().as_secs() as i64;
+
+        ctx.defer().await?;
+        let png = chart(&server.data_path, range, interval).await?;
+
+        ctx.send(
+            poise::CreateReply::default()
+                .attachment(CreateAttachment::bytes(png, CHART_FILE))
+                .embed(
+                    CreateEmbed::new()
+                        .title(format!(
+                            "Players on {} over the last {range_name}",
+                            server.name
+                        ))
+                        .image(format!("attachment://{CHART_FILE}"))
+                        .footer(CreateEmbedFooter::new("Times are in UTC")),
+                ),
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,340 @@
+//! Which Discord user owns which Minecraft account, kept in `links.json` in `DATA_PATH`
+//!
+//! Linking proves ownership with a one-time code whispered to the player in game, which they
+//! either enter back in Discord or say in chat.
+
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use poise::serenity_prelude::{json, Timestamp, UserId};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{Mutex, Notify},
+    time::{self, Duration, Instant},
+};
+
+use crate::{
+    logs::{self, is_player_name, LogTail},
+    server, storage, Context, Error,
+};
+
+const FILE_NAME: &str = "links.json";
+/// How long a code can be used for
+const CODE_LIFETIME: Duration = Duration::from_secs(10 * 60);
+/// Wrong codes allowed before the code is thrown away, so it can't be guessed
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Clone, Deserialize, Serialize)]
+struct Link {
+    user: UserId,
+    name: String,
+    linked: i64,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct Links {
+    links: Vec<Link>,
+}
+
+/// A code sent in game that hasn't been entered yet
+struct Pending {
+    name: String,
+    code: String,
+    expires: Instant,
+    attempts: u32,
+    /// Woken when the code is entered in Discord
+    confirmed: Arc<Notify>,
+}
+
+pub struct LinkStore {
+    path: PathBuf,
+    links: Mutex<Links>,
+    pending: Mutex<HashMap<UserId, Pending>>,
+}
+
+impl LinkStore {
+    pub async fn load(data_path: &Path) -> Result<Self, Error> {
+        let path = data_path.join(FILE_NAME);
+        let links = match tokio::fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Links::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            path,
+            links: Mutex::new(links),
+            pending: Mutex::default(),
+        })
+    }
+
+    async fn save_locked(&self, links: &Links) -> Result<(), Error> {
+        storage::write_atomic(&self.path, &serde_json::to_vec(links)?).await
+    }
+
+    /// The Minecraft name `user` has linked
+    pub async fn name(&self, user: UserId) -> Option<String> {
+        let links = self.links.lock().await;
+        links
+            .links
+            .iter()
+            .find(|link| link.user == user)
+            .map(|link| link.name.clone())
+    }
+
+    /// Every linked user, by lowercase Minecraft name
+    pub async fn users(&self) -> HashMap<String, UserId> {
+        let links = self.links.lock().await;
+        links
+            .links
+            .iter()
+            .map(|link| (link.name.to_lowercase(), link.user))
+            .collect()
+    }
+
+    /// Every linked user with the name they linked
+    pub async fn all(&self) -> HashMap<UserId, String> {
+        let links = self.links.lock().await;
+        links
+            .links
+            .iter()
+            .map(|link| (link.user, link.name.clone()))
+            .collect()
+    }
+
+    /// Swap any user mentions in `targets` for the names they've linked
+    pub async fn resolve(&self, targets: &[String]) -> Result<Vec<String>, Error> {
+        let mut resolved = Vec::with_capacity(targets.len());
+        for target in targets {
+            let mention = target
+                .strip_prefix("<@")
+                .and_then(|id| id.strip_suffix('>'))
+                .map(|id| id.trim_start_matches('!'))
+                .and_then(|id| id.parse::<u64>().ok());
+            match mention {
+                Some(id) => {
+                    let name = self
+                        .name(UserId::new(id))
+                        .await
+                        .ok_or_else(|| format!("{target} hasn't linked a Minecraft account"))?;
+                    resolved.push(name);
+                }
+                None => resolved.push(target.clone()),
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Link `user` to `name`, replacing whatever either was linked to before
+    async fn link(&self, user: UserId, name: &str) -> Result<(), Error> {
+        let mut links = self.links.lock().await;
+        // Knowing the code proves ownership, so an account linked by someone else moves over
+        links
+            .links
+            .retain(|link| link.user != user && !link.name.eq_ignore_ascii_case(name));
+        links.links.push(Link {
+            user,
+            name: name.to_owned(),
+            linked: Timestamp::now().unix_timestamp(),
+        });
+        self.save_locked(&links).await?;
+
+        log::info!("Linked {user} to {name}");
+        Ok(())
+    }
+
+    async fn unlink(&self, user: UserId) -> Result<Option<String>, Error> {
+        let mut links = self.links.lock().await;
+        let Some(index) = links.links.iter().position(|link| link.user == user) else {
+            return Ok(None);
+        };
+        let link = links.links.remove(index);
+        self.save_locked(&links).await?;
+        Ok(Some(link.name))
+    }
+
+    /// Link `user` if `code` is the one they were sent for `name`, returning whether it was
+    async fn confirm(&self, user: UserId, name: &str, code: &str) -> Result<bool, Error> {
+        let pending = {
+            let mut pending = self.pending.lock().await;
+            match pending.get_mut(&user) {
+                Some(p)
+                    if p.name.eq_ignore_ascii_case(name)
+                        && p.code == code
+                        && p.expires > Instant::now() =>
+                {
+                    pending.remove(&user)
+                }
+                Some(p) => {
+                    p.attempts += 1;
+                    if p.attempts >= MAX_ATTEMPTS {
+                        pending.remove(&user);
+                    }
+                    None
+                }
+                None => None,
+            }
+        };
+        let Some(pending) = pending else {
+            return Ok(false);
+        };
+        self.link(user, &pending.name).await?;
+        pending.confirmed.notify_one();
+        Ok(true)
+    }
+}
+
+/// Six random digits
+fn generate_code() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    hasher.write_u128(now.as_nanos());
+    format!("{:06}", hasher.finish() % 1_000_000)
+}
+
+/// Wait for `name` to say `code` in chat, or forever if the log can't be read
+async fn code_in_chat(log: &Path, name: &str, code: &str) {
+    let Ok(mut tail) = LogTail::open(log).await else {
+        return std::future::pending().await;
+    };
+    loop {
+        let Ok(lines) = tail.next_lines().await else {
+            return std::future::pending().await;
+        };
+        let said = lines.iter().any(|line| {
+            logs::chat_message(line).is_some_and(|(player, text)| {
+                player.eq_ignore_ascii_case(name) && text.trim() == code
+            })
+        });
+        if said {
+            return;
+        }
+    }
+}
+
+/// Link your Minecraft account, or enter the code you were sent to finish doing so
+#[poise::command(slash_command)]
+pub async fn link(
+    ctx: Context<'_>,
+    #[description = "Your Minecraft username"] minecraft_name: String,
+    #[description = "The code you were sent in game"] code: Option<String>,
+    #[description = "The server you're playing on, defaults to the first server"]
+    #[autocomplete = "server::autocomplete"]
+    server: Option<String>,
+) -> Result<(), Error> {
+    let links = &ctx.data().links;
+    let user = ctx.author().id;
+
+    if let Some(code) = code {
+        let content = if links.confirm(user, &minecraft_name, code.trim()).await? {
+            ctx.data().role_sync.wake();
+            format!("Linked to {minecraft_name}")
+        } else {
+            "That code is wrong or has expired, run /link again for a new one".to_string()
+        };
+        ctx.send(
+            poise::CreateReply::default()
+                .content(content)
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if !is_player_name(&minecraft_name) {
+        return Err(format!("{minecraft_name} isn't a valid Minecraft username").into());
+    }
+    let config = ctx.data().config.get();
+    let server = config.servers.get(server.as_deref())?;
+
+    ctx.defer_ephemeral().await?;
+
+    let code = generate_code();
+    // serde_json takes care of escaping, though a valid name and digits never need it
+    let message = json::json!([
+        "",
+        { "text": "Your Discord link code is " },
+        { "text": code, "bold": true, "color": "gold" },
+        { "text": ". Say it in chat or enter it with /link in Discord. If you didn't ask for this, ignore it." },
+    ]);
+    let response = server
+        .rcon()?
+        .send_command(&format!("tellraw {minecraft_name} {message}"))
+        .await?;
+    // Only failure has anything to say
+    if !response.is_empty() {
+        return Err(format!(
+            "{minecraft_name} has to be online on {} to link",
+            server.name
+        )
+        .into());
+    }
+
+    let confirmed = Arc::new(Notify::new());
+    links.pending.lock().await.insert(
+        user,
+        Pending {
+            name: minecraft_name.clone(),
+            code: code.clone(),
+            expires: Instant::now() + CODE_LIFETIME,
+            attempts: 0,
+            confirmed: confirmed.clone(),
+        },
+    );
+
+    let reply = ctx
+        .send(poise::CreateReply::default().content(format!(
+            "Sent a code to {minecraft_name} in game. Say it in chat, or run `/link {minecraft_name} code:<code>` within {} minutes.",
+            CODE_LIFETIME.as_secs() / 60
+        )))
+        .await?;
+
+    let linked = tokio::select! {
+        _ = confirmed.notified() => true,
+        _ = code_in_chat(&server.log, &minecraft_name, &code) => {
+            links.confirm(user, &minecraft_name, &code).await?
+        }
+        _ = time::sleep(CODE_LIFETIME) => false,
+    };
+
+    let content = if linked {
+        ctx.data().role_sync.wake();
+        format!("Linked to {minecraft_name}")
+    } else {
+        let mut pending = links.pending.lock().await;
+        if pending.get(&user).is_some_and(|p| p.code == code) {
+            pending.remove(&user);
+        }
+        "The code expired, run /link again for a new one".to_string()
+    };
+    reply
+        .edit(ctx, poise::CreateReply::default().content(content))
+        .await?;
+    Ok(())
+}
+
+/// Forget the Minecraft account you linked
+#[poise::command(slash_command)]
+pub async fn unlink(ctx: Context<'_>) -> Result<(), Error> {
+    let content = match ctx.data().links.unlink(ctx.author().id).await? {
+        Some(name) => {
+            ctx.data().role_sync.wake();
+            format!("Unlinked {name}")
+        }
+        None => "You haven't linked a Minecraft account".to_string(),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
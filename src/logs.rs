@@ -0,0 +1,214 @@
+use std::{
+    io::SeekFrom,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use notify_debouncer_mini::{
+    new_debouncer, notify::RecommendedWatcher, notify::RecursiveMode, DebounceEventResult,
+    Debouncer,
+};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::mpsc::{self, UnboundedReceiver},
+    time::{self, Duration},
+};
+
+use crate::Error;
+
+const DEBOUNCE: Duration = Duration::from_millis(500);
+// Filesystem events aren't always delivered (bind mounts, network shares), so poll as well
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Follows a server log, handling the rotation that happens when the server restarts
+pub struct LogTail {
+    path: PathBuf,
+    inode: u64,
+    offset: u64,
+    partial: Vec<u8>,
+    events: UnboundedReceiver<()>,
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+impl LogTail {
+    /// Start following `path` from its current end
+    pub async fn open(path: &Path) -> Result<Self, Error> {
+        let (tx, events) = mpsc::unbounded_channel();
+        let mut debouncer = new_debouncer(DEBOUNCE, move |res: DebounceEventResult| {
+            if let Err(err) = res {
+                log::warn!("Error watching log: {err}");
+            }
+            let _ = tx.send(());
+        })?;
+
+        // Watch the directory rather than the file so rotation doesn't leave us watching nothing
+        let dir = path.parent().unwrap_or(Path::new("."));
+        debouncer
+            .watcher()
+            .watch(dir, RecursiveMode::NonRecursive)?;
+
+        let (inode, offset) = match tokio::fs::metadata(path).await {
+            Ok(meta) => (meta.ino(), meta.len()),
+            Err(_) => (0, 0),
+        };
+
+        Ok(Self {
+            path: path.to_owned(),
+            inode,
+            offset,
+            partial: Vec::new(),
+            events,
+            _debouncer: debouncer,
+        })
+    }
+
+    /// Wait for the log to change and return any newly completed lines
+    ///
+    /// Cancel safe, so it can be raced against other things without losing lines.
+    pub async fn next_lines(&mut self) -> Result<Vec<String>, Error> {
+        loop {
+            tokio::select! {
+                _ = self.events.recv() => (),
+                _ = time::sleep(POLL_INTERVAL) => (),
+            }
+
+            let lines = self.read_new().await?;
+            if !lines.is_empty() {
+                return Ok(lines);
+            }
+        }
+    }
+
+    async fn read_new(&mut self) -> Result<Vec<String>, Error> {
+        let meta = match tokio::fs::metadata(&self.path).await {
+            Ok(meta) => meta,
+            // Likely mid-rotation, try again on the next event
+            Err(_) => return Ok(Vec::new()),
+        };
+
+        if meta.ino() != self.inode || meta.len() < self.offset {
+            log::info!("{} was rotated", self.path.display());
+            self.inode = meta.ino();
+            self.offset = 0;
+            self.partial.clear();
+        }
+
+        if meta.len() == self.offset {
+            return Ok(Vec::new());
+        }
+
+        let mut file = File::open(&self.path).await?;
+        file.seek(SeekFrom::Start(self.offset)).await?;
+        // Only update the state once everything is read, in case this is cancelled
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).await?;
+        self.offset += read.len() as u64;
+        self.partial.extend(read);
+
+        let end = match self.partial.iter().rposition(|&b| b == b'\n') {
+            Some(end) => end + 1,
+            None => return Ok(Vec::new()),
+        };
+        let rest = self.partial.split_off(end);
+        let complete = std::mem::replace(&mut self.partial, rest);
+
+        Ok(String::from_utf8_lossy(&complete)
+            .lines()
+            .map(|line| line.trim_end_matches('\r').to_owned())
+            .collect())
+    }
+}
+
+/// Extract the message portion of a line logged by the server thread
+///
+/// Handles the vanilla `[12:00:00] [Server thread/INFO]: message` layout, the extended layout
+/// used by Forge and friends, which adds a logger name after the level, and Paper's
+/// `[12:00:00 INFO]: message`, which leaves out the thread.
+pub fn server_message(line: &str) -> Option<&str> {
+    let (prefix, message) = line.split_once("]: ")?;
+    // The closing bracket is part of the separator in the vanilla layout
+    if prefix.contains("[Server thread/INFO") || is_paper_info(prefix) {
+        Some(message)
+    } else {
+        None
+    }
+}
+
+fn is_paper_info(prefix: &str) -> bool {
+    prefix.starts_with('[') && prefix.ends_with(" INFO") && !prefix.contains(']')
+}
+
+/// Split a chat line into the player and what they said
+///
+/// Spigot logs chat from its own thread rather than the server thread, and servers that don't
+/// enforce secure chat mark unsigned messages, so both are accepted.
+pub fn chat_message(line: &str) -> Option<(&str, &str)> {
+    let message = match server_message(line) {
+        Some(message) => message,
+        None => {
+            let (prefix, message) = line.split_once("]: ")?;
+            prefix.contains("[Async Chat Thread").then_some(message)?
+        }
+    };
+    let message = message.strip_prefix("[Not Secure] ").unwrap_or(message);
+    let (player, text) = message.strip_prefix('<')?.split_once("> ")?;
+    is_player_name(player).then_some((player, text))
+}
+
+/// Whether `name` could be a Minecraft username
+pub fn is_player_name(name: &str) -> bool {
+    (1..=16).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Escape characters that Discord would otherwise interpret as markdown
+pub fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '_' | '~' | '`' | '|' | '>' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::server_message;
+
+    #[test]
+    fn server_message_vanilla() {
+        let line = "[12:34:56] [Server thread/INFO]: Steve was slain by Zombie";
+        assert_eq!(server_message(line), Some("Steve was slain by Zombie"));
+    }
+
+    #[test]
+    fn server_message_forge() {
+        let line =
+            "[12:34:56] [Server thread/INFO] [minecraft/MinecraftServer]: Steve fell from a high place";
+        assert_eq!(server_message(line), Some("Steve fell from a high place"));
+    }
+
+    #[test]
+    fn server_message_paper() {
+        let line = "[12:34:56 INFO]: Steve has made the advancement [Stone Age]";
+        assert_eq!(
+            server_message(line),
+            Some("Steve has made the advancement [Stone Age]")
+        );
+    }
+
+    #[test]
+    fn server_message_ignores_other_threads_and_levels() {
+        let lines = [
+            "[12:34:56] [User Authenticator #1/INFO]: UUID of player Steve is 069a79f4-44e9-4726-a5be-fca90e38aaf5",
+            "[12:34:56] [Server thread/WARN]: Can't keep up! Is the server overloaded?",
+            "[12:34:56 WARN]: Can't keep up! Is the server overloaded?",
+            "Starting minecraft server version 1.20.4",
+        ];
+        for line in lines {
+            assert_eq!(server_message(line), None, "{line}");
+        }
+    }
+}
@@ -1,21 +1,39 @@
 use std::{path::PathBuf, sync::Arc};
 
-use futures::{stream, StreamExt};
-use monitor::MonitorService;
-use poise::serenity_prelude::{self as serenity, ChannelId};
-use rcon::RconClient;
-use serde_json::Value;
-use tokio::{
-    signal::unix::{signal, SignalKind},
-    sync::Mutex,
-};
+use applications::ApplicationStore;
+use bans::BanStore;
+use config::ConfigHandle;
+use links::LinkStore;
+use monitor::Registry;
+use poise::serenity_prelude as serenity;
+use role_sync::Syncer;
+use sessions::SessionStore;
+use tokio::signal::unix::{signal, SignalKind};
 use tokio_util::{sync::CancellationToken, task::TaskTracker};
 
-use crate::monitor::ServiceContext;
-
+use crate::monitor::{ServiceContext, Shared};
+
+mod applications;
+mod audit;
+mod bans;
+mod chart;
+mod chat;
+mod config;
+mod history;
+mod links;
+mod logs;
 mod misc;
 mod monitor;
+mod query;
+mod raknet;
 mod rcon;
+mod role_sync;
+mod server;
+mod sessions;
+mod slp;
+mod storage;
+mod uptime;
+mod util;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, Data, Error>;
@@ -23,11 +41,14 @@ type Context<'a> = poise::Context<'a, Data, Error>;
 const DEFAULT_DATA_PATH: &str = "/data";
 
 pub struct Data {
-    server_name: String,
-    server_hostname: String,
-    server_port: u16,
-    rcon: Option<Mutex<RconClient>>,
-    services: (TaskTracker, Arc<Mutex<Vec<Arc<MonitorService>>>>),
+    data_path: PathBuf,
+    config: Arc<ConfigHandle>,
+    applications: ApplicationStore,
+    bans: Arc<BanStore>,
+    links: Arc<LinkStore>,
+    role_sync: Arc<Syncer>,
+    sessions: Arc<SessionStore>,
+    services: (TaskTracker, Arc<Registry>),
     cancel_token: CancellationToken,
 }
 
@@ -39,41 +60,90 @@ async fn main() {
     let data_path = PathBuf::from(data_path);
 
     let token = std::env::var("DISCORD_TOKEN").expect("missing DISCORD_TOKEN");
-    let server_name = std::env::var("SERVER_NAME").unwrap_or_else(|_| "Minecraft Server".into());
-    let server_hostname = std::env::var("SERVER_HOST").unwrap_or_else(|_| "localhost".into());
-    let server_port: u16 =
-        std::env::var("SEVER_PORT").map_or(25565, |p| p.parse().expect("Invalid SERVER_PORT"));
-
-    let mut commands = vec![monitor::monitor(), misc::apt()];
-
-    let rcon = if let Ok(rcon_password) = std::env::var("RCON_PASSWORD") {
-        let rcon_port: u16 =
-            std::env::var("RCON_PORT").map_or(25575, |p| p.parse().expect("Invalid RCON_PORT"));
-
-        let rcon = RconClient::connect((server_hostname.as_ref(), rcon_port), &rcon_password).await;
-        match rcon {
-            Ok(rcon) => {
-                commands.extend([rcon::command(), rcon::say(), rcon::whitelist()]);
-                Some(Mutex::new(rcon))
-            }
-            Err(err) => {
-                log::warn!(
-                    "Unable to connect to rcon (Error: {}) Commands using rcon will be unavailable",
-                    err
-                );
-                None
-            }
-        }
-    } else {
-        log::warn!("No RCON_PASSWORD provided. Commands using rcon will be unavailable");
-        None
-    };
+    let config = ConfigHandle::load(data_path.clone())
+        .await
+        .expect("Invalid configuration");
+    let config = Arc::new(config);
+    let sessions = Arc::new(SessionStore::default());
+    let links = Arc::new(
+        LinkStore::load(&data_path)
+            .await
+            .expect("Unable to load links"),
+    );
+    let applications = ApplicationStore::load(data_path.clone())
+        .await
+        .expect("Unable to load applications");
+    let bans = Arc::new(
+        BanStore::load(&data_path)
+            .await
+            .expect("Unable to load bans"),
+    );
+
+    let commands = vec![
+        monitor::command(),
+        misc::apt(),
+        rcon::command(),
+        rcon::say(),
+        rcon::whitelist(),
+        rcon::ban(),
+        rcon::ban_ip(),
+        rcon::pardon(),
+        rcon::kick(),
+        rcon::banlist(),
+        sessions::seen(),
+        sessions::playtime(),
+        sessions::leaderboard(),
+        history::stats(),
+        uptime::uptime(),
+        applications::apply(),
+        links::link(),
+        links::unlink(),
+        role_sync::rolesync(),
+    ];
 
     let options = poise::FrameworkOptions {
         commands,
+        event_handler: |ctx, event, _framework, data| {
+            Box::pin(async move {
+                match event {
+                    serenity::FullEvent::Message { new_message } => {
+                        monitor::on_message(&ctx.cache, data, new_message).await?
+                    }
+                    serenity::FullEvent::InteractionCreate {
+                        interaction: serenity::Interaction::Component(interaction),
+                    } => applications::on_component(ctx, data, interaction).await?,
+                    serenity::FullEvent::GuildMemberAddition { new_member } => {
+                        role_sync::on_member_change(data, new_member.guild_id, new_member.user.id)
+                            .await
+                    }
+                    serenity::FullEvent::GuildMemberRemoval { guild_id, user, .. } => {
+                        role_sync::on_member_change(data, *guild_id, user.id).await
+                    }
+                    serenity::FullEvent::GuildMemberUpdate {
+                        old_if_available,
+                        event,
+                        ..
+                    } => {
+                        // Nicknames and the like change far more often than roles
+                        let roles_changed = old_if_available
+                            .as_ref()
+                            .is_none_or(|old| old.roles != event.roles);
+                        if roles_changed {
+                            role_sync::on_member_change(data, event.guild_id, event.user.id).await
+                        }
+                    }
+                    _ => (),
+                }
+                Ok(())
+            })
+        },
         ..Default::default()
     };
 
+    // Outlives the client, so that shutdown can wait for whatever services are still doing
+    let tracker = TaskTracker::new();
+    let tracker_clone = tracker.clone();
+
     let framework = poise::Framework::builder()
         .setup(move |ctx, ready, framework| {
             Box::pin(async move {
@@ -96,76 +166,98 @@ async fn main() {
                     ready.guilds.len()
                 );
 
-                let tracker = TaskTracker::new();
+                let tracker = tracker_clone;
                 let cancel_token = CancellationToken::new();
 
-                let services = tokio::fs::read(data_path.join("services.json"))
-                    .await
-                    .unwrap_or_else(|_| b"[]".into());
-                let services: Vec<Value> = serde_json::from_slice(&services)?;
-                let services = stream::iter(services)
-                    .map(|value| {
-                        Arc::new(MonitorService::new(
-                            ctx.http.clone(),
-                            cancel_token.child_token(),
-                            serde_json::from_value(value["channel_id"].clone()).unwrap(),
-                            serde_json::from_value(value["monitor_type"].clone()).unwrap(),
-                        ))
-                    })
-                    .collect::<Vec<_>>()
-                    .await;
+                let shared = Shared {
+                    http: ctx.http.clone(),
+                    config: config.clone(),
+                    sessions: sessions.clone(),
+                    links: links.clone(),
+                };
+                let registry =
+                    Registry::load(data_path.join("services.json"), &shared, &cancel_token).await?;
+                let registry = Arc::new(registry);
 
                 log::info!("Starting services...");
 
+                let services = registry.services().await;
                 let service_count = services.len();
-                let services = Arc::new(Mutex::new(services));
-                for service in &*services.lock().await {
-                    let services = services.clone();
-                    let service = service.clone();
-                    let ctx = ServiceContext::new(services.clone());
+                for service in services {
+                    let ctx = ServiceContext::new(registry.clone());
                     tracker.spawn(async move { service.run(ctx).await });
                 }
 
                 log::info!("Started {} services", service_count);
 
-                let services_clone = services.clone();
+                let bans_clone = bans.clone();
+                let config_clone = config.clone();
+                let token = cancel_token.clone();
+                tracker.spawn(async move { bans_clone.run(config_clone, token).await });
+
+                let config_clone = config.clone();
+                let token = cancel_token.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = config_clone.watch(token).await {
+                        log::error!("Unable to watch for configuration changes: {err}");
+                    }
+                });
+
+                let role_sync = Arc::new(Syncer::default());
+                let role_sync_clone = role_sync.clone();
+                let http = ctx.http.clone();
+                let (config_clone, links_clone) = (config.clone(), links.clone());
+                let token = cancel_token.clone();
+                tracker.spawn(async move {
+                    role_sync_clone
+                        .run(http, config_clone, links_clone, token)
+                        .await
+                });
+
+                let registry_clone = registry.clone();
                 let token = cancel_token.clone();
                 let shard_manager = framework.shard_manager().clone();
                 tokio::spawn(async move {
                     let mut signal = signal(SignalKind::terminate()).unwrap();
                     signal.recv().await.unwrap();
 
-                    log::info!("Stopping client...");
-
-                    shard_manager.shutdown_all().await;
-
                     log::info!("Stopping services...");
 
                     token.cancel();
-                    let services = services_clone.lock().await;
-                    let data = serde_json::to_string(&*services).unwrap();
-                    tokio::fs::write(data_path.join("services.json"), data.as_bytes())
+                    let count = registry_clone
+                        .save()
                         .await
                         .expect("Failed to serialize services");
 
-                    log::info!("Stopped {} services", services.len());
+                    log::info!("Stopped {} services", count);
+
+                    log::info!("Stopping client...");
+
+                    shard_manager.shutdown_all().await;
                 });
 
-                let services = (tracker, services);
+                let services = (tracker, registry);
 
                 Ok(Data {
-                    server_name,
-                    server_hostname,
-                    server_port,
+                    data_path,
+                    config,
+                    applications,
+                    bans,
+                    links,
+                    role_sync,
+                    sessions,
                     services,
-                    rcon,
                     cancel_token,
                 })
             })
         })
         .options(options)
         .build();
-    let intents = serenity::GatewayIntents::non_privileged();
+    // Message content and members are privileged, and have to be enabled for the bot in the
+    // developer portal
+    let intents = serenity::GatewayIntents::non_privileged()
+        | serenity::GatewayIntents::MESSAGE_CONTENT
+        | serenity::GatewayIntents::GUILD_MEMBERS;
     let client = serenity::ClientBuilder::new(token, intents)
         .framework(framework)
         .await;
@@ -175,4 +267,10 @@ async fn main() {
     client.unwrap().start().await.unwrap();
 
     log::info!("Client stopped");
+
+    // Cancelled services may still be finishing something, like a role sync halfway through
+    tracker.close();
+    tracker.wait().await;
+
+    log::info!("Services finished");
 }
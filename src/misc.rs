@@ -12,13 +12,10 @@ pub async fn apt(ctx: Context<'_>, arguments: String) -> Result<(), Error> {
             ctx.say(msg).await?;
 
             // Ignore errors
-            if let Some(rcon) = ctx.data().rcon.as_ref() {
-                let mut rcon = rcon.lock().await;
-
+            let config = ctx.data().config.get();
+            for rcon in config.servers.iter().filter_map(|s| s.rcon.as_ref()) {
                 let _ = rcon
-                    .send_command(&format!(
-                        r#"/tellraw @a {{"text":"...\"Have you mooed today?\"..."}}"#
-                    ))
+                    .send_command(r#"/tellraw @a {"text":"...\"Have you mooed today?\"..."}"#)
                     .await;
             }
         }
@@ -0,0 +1,129 @@
+use std::{collections::HashMap, path::PathBuf, sync::OnceLock};
+
+use futures::future::BoxFuture;
+use poise::serenity_prelude::{json, Color, CreateEmbed, CreateEmbedAuthor, Timestamp};
+use serde::{Deserialize, Serialize};
+
+use super::{Monitor, MonitorContext, MonitorKind};
+use crate::{chat, logs, server::Server, Context, Error};
+
+pub const KIND: MonitorKind = MonitorKind {
+    name: "advancement",
+    tag: "Advancement",
+    create,
+    load: |value| Ok(Box::new(json::from_value::<Advancement>(value)?)),
+};
+
+#[derive(Clone, Copy)]
+enum AdvancementFrame {
+    Task,
+    Goal,
+    Challenge,
+}
+
+impl AdvancementFrame {
+    fn verb(self) -> &'static str {
+        match self {
+            Self::Task => "has made the advancement",
+            Self::Goal => "has reached the goal",
+            Self::Challenge => "has completed the challenge",
+        }
+    }
+
+    // Roughly the colours the game uses for each frame's chat message
+    fn color(self) -> Color {
+        match self {
+            Self::Task => Color::DARK_GREEN,
+            Self::Goal => Color::BLUE,
+            Self::Challenge => Color::PURPLE,
+        }
+    }
+}
+
+/// Split a server message into the player, frame and title if it announces an advancement
+fn advancement_message(message: &str) -> Option<(&str, AdvancementFrame, &str)> {
+    let (player, rest) = message.split_once(' ')?;
+    if !logs::is_player_name(player) {
+        return None;
+    }
+    [
+        AdvancementFrame::Task,
+        AdvancementFrame::Goal,
+        AdvancementFrame::Challenge,
+    ]
+    .into_iter()
+    .find_map(|frame| {
+        let title = rest
+            .strip_prefix(frame.verb())?
+            .strip_prefix(" [")?
+            .strip_suffix(']')?;
+        Some((player, frame, title))
+    })
+}
+
+/// Look up the description of an advancement by its English title
+fn advancement_description(title: &str) -> Option<&'static str> {
+    static DESCRIPTIONS: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+    DESCRIPTIONS
+        .get_or_init(|| {
+            let lang = chat::lang();
+            lang.iter()
+                .filter_map(|(key, title)| {
+                    let key = key.strip_suffix(".title")?;
+                    let description = lang.get(&format!("{key}.description"))?;
+                    Some((title.clone(), description.clone()))
+                })
+                .collect()
+        })
+        .get(title)
+        .map(String::as_str)
+}
+
+/// Posts advancements, goals and challenges from the server log
+#[derive(Deserialize, Serialize)]
+pub struct Advancement {
+    log: PathBuf,
+}
+
+fn create<'a>(
+    _ctx: Context<'a>,
+    server: &'a Server,
+) -> BoxFuture<'a, Result<Box<dyn Monitor>, Error>> {
+    Box::pin(async move {
+        let monitor = Advancement {
+            log: server.log.clone(),
+        };
+        Ok(Box::new(monitor) as _)
+    })
+}
+
+impl Monitor for Advancement {
+    fn serialize(&self) -> Result<json::Value, Error> {
+        Ok(json::to_value(self)?)
+    }
+
+    fn run<'a>(&'a self, ctx: &'a MonitorContext) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(ctx.follow_log(&self.log, advancement_embed))
+    }
+
+    fn describe(&self) -> String {
+        format!("Advancements from {}", self.log.display())
+    }
+}
+
+fn advancement_embed(message: &str) -> Option<CreateEmbed> {
+    let (player, frame, title) = advancement_message(message)?;
+
+    log::info!("{player} {} [{title}]", frame.verb());
+
+    let mut embed = CreateEmbed::new()
+        .author(CreateEmbedAuthor::new(format!("{player} {}", frame.verb())))
+        .title(title)
+        .timestamp(Timestamp::now())
+        .color(frame.color());
+    if let Some(description) = advancement_description(title) {
+        embed = embed.description(description);
+    }
+    Some(embed)
+}
@@ -0,0 +1,139 @@
+use futures::future::BoxFuture;
+use poise::serenity_prelude::{json, EditMessage, MessageId};
+use serde::{Deserialize, Serialize};
+use tokio::time::{self, Duration};
+
+use super::{status::Summary, Monitor, MonitorContext, MonitorKind};
+use crate::{
+    chat::Text,
+    raknet::{self, BedrockStatus},
+    server::Server,
+    Context, Error,
+};
+
+pub const KIND: MonitorKind = MonitorKind {
+    name: "bedrock",
+    tag: "Bedrock",
+    create,
+    load: |value| Ok(Box::new(json::from_value::<Bedrock>(value)?)),
+};
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Keeps a message updated with a Bedrock server's status
+#[derive(Deserialize, Serialize)]
+pub struct Bedrock {
+    name: String,
+    host: String,
+    port: u16,
+    mid: MessageId,
+}
+
+fn create<'a>(
+    ctx: Context<'a>,
+    server: &'a Server,
+) -> BoxFuture<'a, Result<Box<dyn Monitor>, Error>> {
+    Box::pin(async move {
+        let monitor = Bedrock {
+            name: server.name.clone(),
+            host: server.host.clone(),
+            port: server.bedrock_port,
+            mid: ctx.channel_id().say(ctx, "Initializing service").await?.id,
+        };
+        Ok(Box::new(monitor) as _)
+    })
+}
+
+impl Monitor for Bedrock {
+    fn serialize(&self) -> Result<json::Value, Error> {
+        Ok(json::to_value(self)?)
+    }
+
+    fn run<'a>(&'a self, ctx: &'a MonitorContext) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(self.run_bedrock(ctx))
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "Bedrock status of {} ({}:{})",
+            self.name, self.host, self.port
+        )
+    }
+
+    fn stop<'a>(&'a self, ctx: &'a MonitorContext) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            ctx.channel_id
+                .edit_message(
+                    &ctx.http,
+                    self.mid,
+                    EditMessage::new().content("Monitor stopped"),
+                )
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+fn summarize(status: BedrockStatus) -> Summary {
+    let motd = match status.sub_motd.as_str() {
+        "" => status.motd,
+        sub_motd => format!("{}\n{sub_motd}", status.motd),
+    };
+    let version = match status.game_mode.as_str() {
+        "" => status.version,
+        game_mode => format!("{} ({game_mode})", status.version),
+    };
+    Summary {
+        motd: Text::from_legacy(&motd),
+        version: Text::from_legacy(&version).markdown(),
+        online: status.online,
+        max: status.max,
+        // Bedrock doesn't advertise who is online
+        players: Vec::new(),
+        latency: Some(status.latency),
+        ..Summary::default()
+    }
+}
+
+impl Bedrock {
+    async fn run_bedrock(&self, ctx: &MonitorContext) -> Result<(), Error> {
+        let (host, port) = (self.host.as_str(), self.port);
+
+        let mut summary = Summary::default();
+
+        loop {
+            log::info!("Updating Bedrock status for {}:{}", host, port);
+
+            let is_online = match raknet::status(host, port, TIMEOUT).await {
+                Ok(status) => {
+                    summary = summarize(status);
+                    true
+                }
+                Err(err) => {
+                    log::info!("{}:{} is offline: {err}", host, port);
+                    summary.offline();
+                    false
+                }
+            };
+
+            ctx.channel_id
+                .edit_message(
+                    &ctx.http,
+                    self.mid,
+                    EditMessage::new()
+                        .content("")
+                        .embed(summary.embed(&self.name, is_online)),
+                )
+                .await?;
+
+            log::info!("Updated Bedrock status for {}:{}", host, port);
+
+            tokio::select! {
+                _ = ctx.token.cancelled() => break,
+                _ = time::sleep(ctx.config.get().monitors.bedrock_interval()) => ()
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+use std::path::PathBuf;
+
+use futures::future::BoxFuture;
+use poise::serenity_prelude::{
+    json, Cache, CreateAllowedMentions, CreateEmbed, CreateEmbedAuthor, CreateMessage,
+    CreateWebhook, ExecuteWebhook, Message, ReactionType, Webhook,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{Monitor, MonitorContext, MonitorKind};
+use crate::{
+    logs::{self, LogTail},
+    server::Server,
+    Context, Error,
+};
+
+pub const KIND: MonitorKind = MonitorKind {
+    name: "chat",
+    tag: "Chat",
+    create,
+    load: |value| Ok(Box::new(json::from_value::<Chat>(value)?)),
+};
+
+/// Longest Discord message relayed in game, in characters
+const MAX_MESSAGE: usize = 256;
+const WEBHOOK_NAME: &str = "Minecraft Chat";
+/// Reacted to messages that couldn't be relayed
+const FAILED: char = '\u{26A0}';
+
+fn avatar_url(player: &str) -> String {
+    format!("https://mc-heads.net/avatar/{player}/64")
+}
+
+/// Relays chat between the server and the channel
+///
+/// Chat is read from the server log and posted through a webhook, so that each message shows
+/// under the player's name, or as embeds if the bot can't manage webhooks in the channel.
+/// Messages posted in the channel are sent to the game with `tellraw`.
+#[derive(Deserialize, Serialize)]
+pub struct Chat {
+    /// The server's id, looked up again for each message so its RCON settings can change
+    server: String,
+    log: PathBuf,
+    #[serde(default)]
+    webhook: Option<String>,
+}
+
+fn create<'a>(
+    ctx: Context<'a>,
+    server: &'a Server,
+) -> BoxFuture<'a, Result<Box<dyn Monitor>, Error>> {
+    Box::pin(async move {
+        let webhook = match ctx
+            .channel_id()
+            .create_webhook(ctx, CreateWebhook::new(WEBHOOK_NAME))
+            .await
+        {
+            Ok(webhook) => Some(webhook.url()?),
+            Err(err) => {
+                log::warn!("Unable to create a webhook, falling back to embeds: {err}");
+                None
+            }
+        };
+        let monitor = Chat {
+            server: server.id.clone(),
+            log: server.log.clone(),
+            webhook,
+        };
+        Ok(Box::new(monitor) as _)
+    })
+}
+
+impl Monitor for Chat {
+    fn serialize(&self) -> Result<json::Value, Error> {
+        Ok(json::to_value(self)?)
+    }
+
+    fn run<'a>(&'a self, ctx: &'a MonitorContext) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(self.run_chat(ctx))
+    }
+
+    fn describe(&self) -> String {
+        format!("Chat with {} from {}", self.server, self.log.display())
+    }
+
+    fn stop<'a>(&'a self, ctx: &'a MonitorContext) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            if let Some(url) = &self.webhook {
+                Webhook::from_url(&ctx.http, url)
+                    .await?
+                    .delete(&ctx.http)
+                    .await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn message<'a>(
+        &'a self,
+        ctx: &'a MonitorContext,
+        cache: &'a Cache,
+        message: &'a Message,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            if let Err(err) = self.relay(ctx, cache, message).await {
+                log::warn!("Unable to relay message to {}: {err}", self.server);
+                let reaction = ReactionType::Unicode(FAILED.to_string());
+                message.react(&ctx.http, reaction).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Chat {
+    async fn run_chat(&self, ctx: &MonitorContext) -> Result<(), Error> {
+        let webhook = match &self.webhook {
+            Some(url) => match Webhook::from_url(&ctx.http, url).await {
+                Ok(webhook) => Some(webhook),
+                Err(err) => {
+                    log::warn!("Webhook in {} is gone, using embeds: {err}", ctx.channel_id);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let mut tail = LogTail::open(&self.log).await?;
+
+        log::info!(
+            "Relaying chat from {} in {}",
+            self.log.display(),
+            ctx.channel_id
+        );
+
+        loop {
+            let lines = tokio::select! {
+                _ = ctx.token.cancelled() => break,
+                lines = tail.next_lines() => lines?,
+            };
+
+            for line in lines {
+                let Some((player, text)) = logs::chat_message(&line) else {
+                    continue;
+                };
+                let text = logs::escape_markdown(text);
+                // Nobody in game should be able to ping the channel
+                let mentions = CreateAllowedMentions::new();
+
+                let sent = match &webhook {
+                    Some(webhook) => {
+                        let execute = ExecuteWebhook::new()
+                            .username(player)
+                            .avatar_url(avatar_url(player))
+                            .content(&text)
+                            .allowed_mentions(mentions.clone());
+                        // Discord rejects some usernames, which an embed can still show
+                        match webhook.execute(&ctx.http, false, execute).await {
+                            Ok(_) => true,
+                            Err(err) => {
+                                log::warn!("Webhook rejected chat from {player}: {err}");
+                                false
+                            }
+                        }
+                    }
+                    None => false,
+                };
+                if !sent {
+                    let embed = CreateEmbed::new()
+                        .author(CreateEmbedAuthor::new(player).icon_url(avatar_url(player)))
+                        .description(text);
+                    ctx.channel_id
+                        .send_message(
+                            &ctx.http,
+                            CreateMessage::new().embed(embed).allowed_mentions(mentions),
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send a Discord message to everyone in game
+    async fn relay(
+        &self,
+        ctx: &MonitorContext,
+        cache: &Cache,
+        message: &Message,
+    ) -> Result<(), Error> {
+        // Players know each other by their Minecraft names
+        let author = match ctx.links.name(message.author.id).await {
+            Some(name) => name,
+            None => message
+                .member
+                .as_ref()
+                .and_then(|member| member.nick.clone())
+                .or_else(|| message.author.global_name.clone())
+                .unwrap_or_else(|| message.author.name.clone()),
+        };
+
+        let mut text = message.content_safe(cache);
+        for attachment in &message.attachments {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(&format!("[{}]", attachment.filename));
+        }
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        let command = format!("tellraw @a {}", tellraw(&author, &text));
+        let config = ctx.config.get();
+        let rcon = config.servers.get(Some(&self.server))?.rcon()?;
+        rcon.send_command(&command).await?;
+        Ok(())
+    }
+}
+
+/// The text component for a Discord message, with `§` removed so nobody can sneak in
+/// formatting codes
+fn tellraw(author: &str, text: &str) -> String {
+    let clean = |s: &str| s.replace('\u{A7}', "");
+    let mut text = clean(text);
+    if let Some((end, _)) = text.char_indices().nth(MAX_MESSAGE) {
+        text.truncate(end);
+        text.push('\u{2026}');
+    }
+    // serde_json takes care of quotes, backslashes and control characters
+    json::json!([
+        "",
+        { "text": "[Discord] ", "color": "blue" },
+        { "text": format!("<{}> ", clean(author)) },
+        { "text": text },
+    ])
+    .to_string()
+}
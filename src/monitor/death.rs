@@ -0,0 +1,125 @@
+use std::path::PathBuf;
+
+use futures::future::BoxFuture;
+use poise::serenity_prelude::{json, Color, CreateEmbed, Timestamp};
+use serde::{Deserialize, Serialize};
+
+use super::{Monitor, MonitorContext, MonitorKind};
+use crate::{logs, server::Server, Context, Error};
+
+pub const KIND: MonitorKind = MonitorKind {
+    name: "death",
+    tag: "Death",
+    create,
+    load: |value| Ok(Box::new(json::from_value::<Death>(value)?)),
+};
+
+// The part of each vanilla death message that follows the victim's name,
+// shortened to the prefix shared by its variants (`death.attack.*` in the language files)
+const DEATH_MESSAGES: &[&str] = &[
+    "blew up",
+    "burned to death",
+    "didn't want to live in the same world as",
+    "died",
+    "discovered the floor was lava",
+    "drowned",
+    "experienced kinetic energy",
+    "fell",
+    "froze to death",
+    "hit the ground too hard",
+    "left the confines of this world",
+    "removed an elytra while flying",
+    "starved to death",
+    "suffocated",
+    "tried to swim in lava",
+    "walked into",
+    "was blown up",
+    "was burnt to a crisp",
+    "was doomed to fall",
+    "was fireballed",
+    "was frozen to death",
+    "was impaled",
+    "was killed",
+    "was obliterated",
+    "was poked to death",
+    "was pricked to death",
+    "was pummeled",
+    "was roasted in dragon's breath",
+    "was shot",
+    "was skewered",
+    "was slain",
+    "was smashed",
+    "was speared",
+    "was squashed",
+    "was squished",
+    "was stomped",
+    "was stung to death",
+    "was struck by lightning",
+    "went off with a bang",
+    "went up in flames",
+    "withered away",
+];
+
+/// Split a server message into the victim and the rest of the message if it is a death message
+fn death_message(message: &str) -> Option<(&str, &str)> {
+    let (player, rest) = message.split_once(' ')?;
+    if !logs::is_player_name(player) {
+        return None;
+    }
+    DEATH_MESSAGES
+        .iter()
+        .any(|death| {
+            rest.strip_prefix(death)
+                .is_some_and(|tail| tail.is_empty() || tail.starts_with([' ', ',']))
+        })
+        .then_some((player, rest))
+}
+
+/// Posts vanilla death messages from the server log
+#[derive(Deserialize, Serialize)]
+pub struct Death {
+    log: PathBuf,
+}
+
+fn create<'a>(
+    _ctx: Context<'a>,
+    server: &'a Server,
+) -> BoxFuture<'a, Result<Box<dyn Monitor>, Error>> {
+    Box::pin(async move {
+        let monitor = Death {
+            log: server.log.clone(),
+        };
+        Ok(Box::new(monitor) as _)
+    })
+}
+
+impl Monitor for Death {
+    fn serialize(&self) -> Result<json::Value, Error> {
+        Ok(json::to_value(self)?)
+    }
+
+    fn run<'a>(&'a self, ctx: &'a MonitorContext) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(ctx.follow_log(&self.log, death_embed))
+    }
+
+    fn describe(&self) -> String {
+        format!("Deaths from {}", self.log.display())
+    }
+}
+
+fn death_embed(message: &str) -> Option<CreateEmbed> {
+    let (player, rest) = death_message(message)?;
+
+    log::info!("{player} {rest}");
+
+    Some(
+        CreateEmbed::new()
+            .description(format!(
+                "\u{1F480} **{}** {}",
+                logs::escape_markdown(player),
+                logs::escape_markdown(rest)
+            ))
+            .timestamp(Timestamp::now())
+            .color(Color::DARK_RED),
+    )
+}
@@ -0,0 +1,500 @@
+use std::{path::Path, sync::Arc, time::Instant};
+
+use futures::future::BoxFuture;
+use poise::serenity_prelude::{
+    json, Cache, ChannelId, CreateEmbed, CreateMessage, Http, Message, MessageBuilder, Timestamp,
+};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::{self, ConfigHandle},
+    links::LinkStore,
+    logs::{self, LogTail},
+    server::Server,
+    sessions::SessionStore,
+    Context, Data, Error,
+};
+pub use registry::Registry;
+use supervisor::Health;
+
+mod advancement;
+mod bedrock;
+mod bridge;
+mod death;
+mod players;
+mod registry;
+mod status;
+mod supervisor;
+
+type CreateFn =
+    for<'a> fn(Context<'a>, &'a Server) -> BoxFuture<'a, Result<Box<dyn Monitor>, Error>>;
+type LoadFn = fn(json::Value) -> Result<Box<dyn Monitor>, Error>;
+
+/// Every kind of monitor that can be started, in the order they're offered to users
+pub const MONITORS: &[MonitorKind] = &[
+    status::KIND,
+    bedrock::KIND,
+    players::KIND,
+    advancement::KIND,
+    death::KIND,
+    bridge::KIND,
+];
+
+/// Something that runs in a channel until it is stopped
+pub trait Monitor: Send + Sync {
+    /// The parameters needed to recreate this monitor with [`MonitorKind::load`]
+    fn serialize(&self) -> Result<json::Value, Error>;
+
+    /// Run until `ctx.token` is cancelled.
+    /// Returning before then, successfully or not, removes the monitor.
+    fn run<'a>(&'a self, ctx: &'a MonitorContext) -> BoxFuture<'a, Result<(), Error>>;
+
+    /// A short, human readable summary of what is being monitored
+    fn describe(&self) -> String;
+
+    /// Clean up after the monitor is stopped by a user
+    fn stop<'a>(&'a self, _ctx: &'a MonitorContext) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Handle a message someone posted in the monitor's channel
+    fn message<'a>(
+        &'a self,
+        _ctx: &'a MonitorContext,
+        _cache: &'a Cache,
+        _message: &'a Message,
+    ) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Registry entry describing how to create and restore a kind of [`Monitor`]
+pub struct MonitorKind {
+    /// Name offered as a choice to `/monitor start`
+    pub name: &'static str,
+    /// Key the monitor's parameters are stored under in `services.json`
+    pub tag: &'static str,
+    /// Create a new monitor from the invoking command
+    pub create: CreateFn,
+    /// Restore a monitor from the output of [`Monitor::serialize`]
+    pub load: LoadFn,
+}
+
+impl MonitorKind {
+    fn from_tag(tag: &str) -> Option<&'static Self> {
+        MONITORS.iter().find(|kind| kind.tag == tag)
+    }
+}
+
+/// What every monitor has access to, whichever channel it runs in
+#[derive(Clone)]
+pub struct Shared {
+    pub http: Arc<Http>,
+    pub config: Arc<ConfigHandle>,
+    pub sessions: Arc<SessionStore>,
+    pub links: Arc<LinkStore>,
+}
+
+impl Shared {
+    pub fn from_ctx(ctx: Context<'_>) -> Self {
+        let data = ctx.data();
+        Self {
+            http: ctx.serenity_context().http.clone(),
+            config: data.config.clone(),
+            sessions: data.sessions.clone(),
+            links: data.links.clone(),
+        }
+    }
+}
+
+/// What a running monitor has access to
+pub struct MonitorContext {
+    pub http: Arc<Http>,
+    pub config: Arc<ConfigHandle>,
+    pub sessions: Arc<SessionStore>,
+    pub links: Arc<LinkStore>,
+    pub channel_id: ChannelId,
+    pub token: CancellationToken,
+}
+
+impl MonitorContext {
+    /// Post an embed for every server message in `log` that `embed` produces one for
+    pub async fn follow_log(
+        &self,
+        log: &Path,
+        embed: impl Fn(&str) -> Option<CreateEmbed>,
+    ) -> Result<(), Error> {
+        let mut tail = LogTail::open(log).await?;
+
+        log::info!("Watching {} in {}", log.display(), self.channel_id);
+
+        loop {
+            let lines = tokio::select! {
+                _ = self.token.cancelled() => break,
+                lines = tail.next_lines() => lines?,
+            };
+
+            for line in lines {
+                let Some(embed) = logs::server_message(&line).and_then(&embed) else {
+                    continue;
+                };
+
+                self.channel_id
+                    .send_message(&self.http, CreateMessage::new().embed(embed))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ServiceContext {
+    registry: Arc<Registry>,
+}
+
+impl ServiceContext {
+    pub fn new(registry: Arc<Registry>) -> Self {
+        Self { registry }
+    }
+
+    pub fn from_ctx(ctx: Context<'_>) -> Self {
+        Self {
+            registry: ctx.data().services.1.clone(),
+        }
+    }
+}
+
+pub struct MonitorService {
+    ctx: MonitorContext,
+    kind: &'static MonitorKind,
+    monitor: Box<dyn Monitor>,
+    health: std::sync::Mutex<Health>,
+}
+
+impl Serialize for MonitorService {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let params = self
+            .monitor
+            .serialize()
+            .map_err(serde::ser::Error::custom)?;
+        let mut state = serializer.serialize_struct("MonitorService", 2)?;
+        state.serialize_field("channel_id", &self.ctx.channel_id)?;
+        state.serialize_field("monitor_type", &json::json!({ self.kind.tag: params }))?;
+        state.end()
+    }
+}
+
+impl MonitorService {
+    pub fn new(
+        shared: &Shared,
+        token: CancellationToken,
+        channel_id: ChannelId,
+        kind: &'static MonitorKind,
+        monitor: Box<dyn Monitor>,
+    ) -> Self {
+        let shared = shared.clone();
+        Self {
+            ctx: MonitorContext {
+                http: shared.http,
+                config: shared.config,
+                sessions: shared.sessions,
+                links: shared.links,
+                channel_id,
+                token,
+            },
+            kind,
+            monitor,
+            health: Default::default(),
+        }
+    }
+
+    /// Restore a service from an entry in `services.json`
+    pub fn from_value(
+        shared: &Shared,
+        token: CancellationToken,
+        value: json::Value,
+    ) -> Result<Self, Error> {
+        let channel_id = json::from_value(value["channel_id"].clone())?;
+        let (tag, params) = value["monitor_type"]
+            .as_object()
+            .and_then(|object| object.iter().next())
+            .ok_or("Missing monitor_type")?;
+        let kind = MonitorKind::from_tag(tag).ok_or_else(|| format!("Unknown monitor {tag}"))?;
+        let monitor = (kind.load)(params.clone())?;
+        Ok(Self::new(shared, token, channel_id, kind, monitor))
+    }
+
+    pub fn channel_id(&self) -> ChannelId {
+        self.ctx.channel_id
+    }
+
+    pub fn describe(&self) -> String {
+        self.monitor.describe()
+    }
+
+    pub fn cancel(&self) {
+        self.ctx.token.cancel()
+    }
+
+    /// Number of failed runs since the service started and the most recent error
+    pub fn failures(&self) -> (u32, Option<(Timestamp, String)>) {
+        let health = self.health.lock().unwrap();
+        (health.total, health.last_error.clone())
+    }
+
+    /// Run the monitor, restarting it after transient failures
+    pub async fn run(&self, ctx: ServiceContext) -> Result<(), Error> {
+        loop {
+            let started = Instant::now();
+            let res = self.monitor.run(&self.ctx).await;
+
+            // Cancelled services were either stopped, which already removed them,
+            // or are being shut down and need to be saved
+            if self.ctx.token.is_cancelled() {
+                break;
+            }
+
+            let err = match res {
+                Ok(()) => break,
+                Err(err) if supervisor::is_fatal(&err) => {
+                    log::error!("Service in {} failed: {err}", self.channel_id());
+                    break;
+                }
+                Err(err) => err,
+            };
+
+            let (delay, consecutive) = {
+                let mut health = self.health.lock().unwrap();
+                (health.record(started, &err), health.consecutive)
+            };
+
+            log::warn!(
+                "Service in {} failed ({consecutive} in a row), restarting in {}s: {err}",
+                self.channel_id(),
+                delay.as_secs()
+            );
+
+            if consecutive == supervisor::WARN_AFTER {
+                let msg = MessageBuilder::new()
+                    .push("\u{26A0}\u{FE0F} This monitor has failed ")
+                    .push(consecutive.to_string())
+                    .push(" times in a row and will keep retrying. Last error: ")
+                    .push_mono_safe(err.to_string())
+                    .build();
+                // Failing to warn shouldn't stop the retries
+                let _ = self.ctx.channel_id.say(&self.ctx.http, msg).await;
+            }
+
+            tokio::select! {
+                _ = self.ctx.token.cancelled() => break,
+                _ = time::sleep(delay) => (),
+            }
+        }
+
+        log::info!("Service in {} finished", self.channel_id());
+
+        if !self.ctx.token.is_cancelled() {
+            ctx.registry.remove(self.channel_id()).await?;
+
+            log::info!("Removed service in {}", self.channel_id());
+        }
+        Ok(())
+    }
+
+    async fn stop(&self) -> Result<(), Error> {
+        self.cancel();
+        self.monitor.stop(&self.ctx).await
+    }
+}
+
+/// Pass a message to the monitor running in its channel, if there is one
+pub async fn on_message(cache: &Cache, data: &Data, message: &Message) -> Result<(), Error> {
+    // Whatever a monitor posts itself mustn't come straight back to it
+    if message.author.bot || message.webhook_id.is_some() {
+        return Ok(());
+    }
+    let Some(service) = data.services.1.get(message.channel_id).await else {
+        return Ok(());
+    };
+    service.monitor.message(&service.ctx, cache, message).await
+}
+
+/// The `/monitor` command with the start choices filled in from [`MONITORS`]
+pub fn command() -> poise::Command<Data, Error> {
+    let mut command = monitor();
+    for subcommand in &mut command.subcommands {
+        for parameter in &mut subcommand.parameters {
+            if parameter.name == "type" {
+                parameter.choices = MONITORS
+                    .iter()
+                    .map(|kind| poise::CommandParameterChoice {
+                        name: kind.name.into(),
+                        localizations: Default::default(),
+                        __non_exhaustive: (),
+                    })
+                    .collect();
+            }
+        }
+    }
+    command
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "config::check_monitor",
+    subcommands("sub::start", "sub::stop", "sub::list"),
+    subcommand_required
+)]
+pub async fn monitor(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+pub mod sub {
+    use std::io::{self, ErrorKind};
+    use std::sync::Arc;
+
+    use poise::serenity_prelude::MessageBuilder;
+
+    use crate::monitor::{MonitorKind, MonitorService, MONITORS};
+    use crate::server::{self, Server};
+    use crate::{Context, Error};
+
+    use super::{ServiceContext, Shared};
+
+    async fn start_service(
+        ctx: Context<'_>,
+        kind: &'static MonitorKind,
+        server: &Server,
+    ) -> Result<(), Error> {
+        let monitor = (kind.create)(ctx, server).await?;
+
+        ctx.defer_ephemeral().await?;
+
+        let service = MonitorService::new(
+            &Shared::from_ctx(ctx),
+            ctx.data().cancel_token.child_token(),
+            ctx.channel_id(),
+            kind,
+            monitor,
+        );
+
+        let service = Arc::new(service);
+        let (tracker, registry) = &ctx.data().services;
+        if !registry.add(service.clone()).await? {
+            return Err(Box::new(io::Error::new(
+                ErrorKind::AlreadyExists,
+                "A monitor service already exists in this channel",
+            )));
+        }
+        let sctx = ServiceContext::from_ctx(ctx);
+        tracker.spawn(async move { service.run(sctx).await });
+
+        log::info!("New service started in {}", ctx.channel_id());
+
+        Ok(())
+    }
+
+    /// Start a monitor service in this channel
+    #[poise::command(slash_command)]
+    pub async fn start(
+        ctx: Context<'_>,
+        // Choices are filled in from the registry by `monitor::command`
+        #[rename = "type"] monitor_type: usize,
+        #[description = "Defaults to the first server"]
+        #[autocomplete = "server::autocomplete"]
+        server: Option<String>,
+    ) -> Result<(), Error> {
+        let config = ctx.data().config.get();
+        let server = config.servers.get(server.as_deref())?;
+        let channel_id = ctx.channel_id();
+        let kind = MONITORS.get(monitor_type).ok_or_else(|| {
+            Box::new(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Unknown monitor type",
+            ))
+        })?;
+        if ctx.data().services.1.get(channel_id).await.is_some() {
+            Err(Box::new(io::Error::new(
+                ErrorKind::AlreadyExists,
+                "A monitor service already exists in this channel",
+            )))
+        } else {
+            log::info!(
+                "Starting new {} service for {} in {}",
+                kind.name,
+                server.id,
+                ctx.channel_id()
+            );
+
+            start_service(ctx, kind, server).await?;
+
+            ctx.say("Started service").await?;
+
+            Ok(())
+        }
+    }
+
+    /// Stop the service running in this channel
+    #[poise::command(slash_command)]
+    pub async fn stop(ctx: Context<'_>) -> Result<(), Error> {
+        let channel_id = ctx.channel_id();
+        let service = ctx.data().services.1.remove(channel_id).await?;
+        if let Some(service) = service {
+            log::info!("Stopping services in {}...", ctx.channel_id());
+
+            if let Err(err) = service.stop().await {
+                log::warn!("Error stopping service in {}: {err}", ctx.channel_id());
+            }
+            ctx.say("Service stopped").await?;
+
+            log::info!("Service in {} stopped", ctx.channel_id());
+            Ok(())
+        } else {
+            Err(Box::new(io::Error::new(
+                ErrorKind::NotFound,
+                "This channel is not running a service",
+            )))
+        }
+    }
+
+    /// List the services running in this server
+    #[poise::command(slash_command)]
+    pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+        let Some(guild_id) = ctx.guild_id() else {
+            return Ok(());
+        };
+        let channels = guild_id.channels(ctx).await?;
+
+        let mut msg = MessageBuilder::new();
+        for service in ctx.data().services.1.services().await {
+            if channels.contains_key(&service.channel_id()) {
+                msg.mention(&service.channel_id())
+                    .push(": ")
+                    .push_line_safe(service.describe());
+
+                if let (failures @ 1.., Some((at, err))) = service.failures() {
+                    msg.push(format!(
+                        "-# {failures} failures, last <t:{}:R>: ",
+                        at.unix_timestamp()
+                    ))
+                    .push_mono_line_safe(err);
+                }
+            }
+        }
+        let msg = msg.build();
+
+        let msg = if msg.is_empty() {
+            "No services are running".into()
+        } else {
+            msg
+        };
+        ctx.send(poise::CreateReply::default().content(msg).ephemeral(true))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,347 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use futures::future::BoxFuture;
+use poise::serenity_prelude::{json, Color, CreateEmbed, CreateMessage, Timestamp};
+use serde::{Deserialize, Serialize};
+use tokio::time::{self, Duration, Instant};
+
+use super::{Monitor, MonitorContext, MonitorKind};
+use crate::{
+    logs::{self, escape_markdown, is_player_name, LogTail},
+    query,
+    server::Server,
+    sessions::format_duration,
+    slp::{self, Timeouts},
+    storage, Context, Error,
+};
+
+pub const KIND: MonitorKind = MonitorKind {
+    name: "players",
+    tag: "Players",
+    create,
+    load: |value| Ok(Box::new(json::from_value::<Players>(value)?)),
+};
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+/// Failed polls in a row before everyone is taken to have left, so one slow poll doesn't end
+/// every session
+const OFFLINE_AFTER: u32 = 3;
+
+/// Announces players joining and leaving and records their sessions
+///
+/// Follows the server log when it can be read, and otherwise polls the server for who's online.
+#[derive(Deserialize, Serialize)]
+pub struct Players {
+    name: String,
+    host: String,
+    port: u16,
+    #[serde(default)]
+    query_port: Option<u16>,
+    #[serde(default)]
+    log: Option<PathBuf>,
+    /// Where sessions are recorded, if anywhere
+    #[serde(default)]
+    data_path: Option<PathBuf>,
+}
+
+fn create<'a>(
+    _ctx: Context<'a>,
+    server: &'a Server,
+) -> BoxFuture<'a, Result<Box<dyn Monitor>, Error>> {
+    Box::pin(async move {
+        let log = match tokio::fs::metadata(&server.log).await {
+            Ok(_) => Some(server.log.clone()),
+            Err(_) => None,
+        };
+        let monitor = Players {
+            name: server.name.clone(),
+            host: server.host.clone(),
+            port: server.port,
+            query_port: server.query_port,
+            log,
+            data_path: Some(server.data_path.clone()),
+        };
+        Ok(Box::new(monitor) as _)
+    })
+}
+
+impl Monitor for Players {
+    fn serialize(&self) -> Result<json::Value, Error> {
+        Ok(json::to_value(self)?)
+    }
+
+    fn run<'a>(&'a self, ctx: &'a MonitorContext) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            // Only the first players monitor of a server records sessions, any other would close
+            // its sessions and record everything twice
+            let claim = self
+                .data_path
+                .as_deref()
+                .and_then(|data_path| storage::claim("players", data_path));
+            let recording = claim.as_ref().map(storage::Claim::data_path);
+
+            // Whoever left while nobody was watching can't be known, so start from scratch
+            if let Some(data_path) = recording {
+                ctx.sessions.close_all(data_path).await?;
+            }
+
+            match &self.log {
+                Some(log) => self.follow_log(ctx, log, recording).await,
+                None => self.poll_players(ctx, recording).await,
+            }
+        })
+    }
+
+    fn describe(&self) -> String {
+        match &self.log {
+            Some(log) => format!("Players joining and leaving {}", log.display()),
+            None => format!(
+                "Players joining and leaving {} ({}:{})",
+                self.name, self.host, self.port
+            ),
+        }
+    }
+}
+
+/// Who a poll saw online
+struct PlayerList {
+    names: HashSet<String>,
+    /// Whether everyone online is in `names`, rather than a sample of them
+    complete: bool,
+}
+
+impl Players {
+    async fn poll(&self) -> Result<PlayerList, Error> {
+        let (host, port) = (self.host.as_str(), self.port);
+
+        // Query lists everyone, so it's worth trying first
+        if let Some(query_port) = self.query_port {
+            match query::full_stat(host, query_port, QUERY_TIMEOUT).await {
+                Ok(stat) => {
+                    return Ok(PlayerList {
+                        names: stat.players.into_iter().collect(),
+                        complete: true,
+                    })
+                }
+                Err(err) => log::info!("Query of {}:{} failed: {err}", host, query_port),
+            }
+        }
+
+        let status = slp::status(host, port, Timeouts::default()).await?;
+        let Some(players) = status.response.players else {
+            return Ok(PlayerList {
+                names: HashSet::new(),
+                complete: true,
+            });
+        };
+        // Plugins like to fill the sample with decorative text, which can't be a name
+        let names: HashSet<String> = players
+            .sample
+            .into_iter()
+            .map(|player| player.name)
+            .filter(|name| is_player_name(name))
+            .collect();
+        let complete = names.len() as u64 >= players.online;
+        Ok(PlayerList { names, complete })
+    }
+
+    /// Poll for who's online, recording sessions to `recording` if it's set
+    async fn poll_players(
+        &self,
+        ctx: &MonitorContext,
+        recording: Option<&Path>,
+    ) -> Result<(), Error> {
+        // When each player was first seen, unknown for those already online when we started
+        let mut online: Option<HashMap<String, Option<Instant>>> = None;
+        let mut last_polled = Instant::now();
+        let mut failures = 0;
+
+        loop {
+            log::info!("Polling players on {}:{}", self.host, self.port);
+
+            let result = self.poll().await;
+            if result.is_ok() {
+                last_polled = Instant::now();
+                failures = 0;
+            }
+            match result {
+                Ok(list) => match &mut online {
+                    // Nobody joined, the monitor just started
+                    None => {
+                        if let Some(data_path) = recording {
+                            for name in &list.names {
+                                ctx.sessions.join(data_path, name).await?;
+                            }
+                        }
+                        online = Some(list.names.into_iter().map(|name| (name, None)).collect())
+                    }
+                    Some(online) => {
+                        let now = Instant::now();
+                        for name in &list.names {
+                            if !online.contains_key(name) {
+                                online.insert(name.clone(), Some(now));
+                                self.joined(ctx, recording, name).await?;
+                            }
+                        }
+
+                        // Someone missing from a sample may well still be online
+                        if list.complete {
+                            let left: Vec<String> = online
+                                .keys()
+                                .filter(|name| !list.names.contains(*name))
+                                .cloned()
+                                .collect();
+                            for name in left {
+                                let since = online.remove(&name).flatten();
+                                self.left(ctx, recording, &name, since.map(|t| now - t))
+                                    .await?;
+                            }
+                        }
+
+                        if let Some(data_path) = recording {
+                            let names: Vec<String> = list.names.into_iter().collect();
+                            ctx.sessions.seen(data_path, &names).await?;
+                        }
+                    }
+                },
+                Err(err) => {
+                    log::info!("Unable to poll {}:{}: {err}", self.host, self.port);
+                    failures += 1;
+                    // Nobody plays on a server that's down, and the downtime isn't playtime
+                    let online = online.as_mut().filter(|online| !online.is_empty());
+                    if let (OFFLINE_AFTER, Some(online)) = (failures, online) {
+                        if let Some(data_path) = recording {
+                            ctx.sessions.close_all(data_path).await?;
+                        }
+                        for (name, since) in online.drain() {
+                            let session = since.map(|since| last_polled - since);
+                            post(ctx, left_embed(&name, session)).await?;
+                        }
+                    }
+                }
+            }
+
+            tokio::select! {
+                _ = ctx.token.cancelled() => break,
+                _ = time::sleep(ctx.config.get().monitors.players_interval()) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Follow `log` for who joins and leaves, recording sessions to `recording` if it's set
+    async fn follow_log(
+        &self,
+        ctx: &MonitorContext,
+        log: &Path,
+        recording: Option<&Path>,
+    ) -> Result<(), Error> {
+        let mut tail = LogTail::open(log).await?;
+
+        log::info!(
+            "Watching {} for players in {}",
+            log.display(),
+            ctx.channel_id
+        );
+
+        // The log says nothing while players stay online, so note that they still are, for
+        // when their sessions have to be closed without a leave message
+        let mut still_online = time::interval(ctx.config.get().monitors.players_interval());
+
+        loop {
+            let lines = tokio::select! {
+                _ = ctx.token.cancelled() => break,
+                lines = tail.next_lines() => lines?,
+                _ = still_online.tick() => {
+                    if let Some(data_path) = recording {
+                        ctx.sessions.seen_online(data_path).await?;
+                    }
+                    continue;
+                }
+            };
+
+            for line in lines {
+                match logs::server_message(&line).and_then(join_message) {
+                    Some((name, true)) => self.joined(ctx, recording, name).await?,
+                    Some((name, false)) => self.left(ctx, recording, name, None).await?,
+                    None => (),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn joined(
+        &self,
+        ctx: &MonitorContext,
+        recording: Option<&Path>,
+        name: &str,
+    ) -> Result<(), Error> {
+        if let Some(data_path) = recording {
+            ctx.sessions.join(data_path, name).await?;
+        }
+        post(ctx, joined_embed(name)).await
+    }
+
+    /// Announce `name` leaving, with how long they played if the sessions or `session` know
+    async fn left(
+        &self,
+        ctx: &MonitorContext,
+        recording: Option<&Path>,
+        name: &str,
+        session: Option<Duration>,
+    ) -> Result<(), Error> {
+        let recorded = match recording {
+            Some(data_path) => ctx.sessions.leave(data_path, name).await?,
+            None => None,
+        };
+        post(ctx, left_embed(name, recorded.or(session))).await
+    }
+}
+
+/// The player in a join or leave message, and whether they joined
+fn join_message(message: &str) -> Option<(&str, bool)> {
+    let (name, joined) = match message.strip_suffix(" joined the game") {
+        Some(name) => (name, true),
+        None => (message.strip_suffix(" left the game")?, false),
+    };
+    is_player_name(name).then_some((name, joined))
+}
+
+async fn post(ctx: &MonitorContext, embed: CreateEmbed) -> Result<(), Error> {
+    ctx.channel_id
+        .send_message(&ctx.http, CreateMessage::new().embed(embed))
+        .await?;
+    Ok(())
+}
+
+fn joined_embed(name: &str) -> CreateEmbed {
+    log::info!("{name} joined");
+
+    CreateEmbed::new()
+        .description(format!("\u{1F44B} **{}** joined", escape_markdown(name)))
+        .timestamp(Timestamp::now())
+        .color(Color::FOOYOO)
+}
+
+fn left_embed(name: &str, session: Option<Duration>) -> CreateEmbed {
+    log::info!("{name} left");
+
+    let description = match session {
+        Some(session) => format!(
+            "\u{1F6AA} **{}** left after {}",
+            escape_markdown(name),
+            format_duration(session)
+        ),
+        None => format!("\u{1F6AA} **{}** left", escape_markdown(name)),
+    };
+    CreateEmbed::new()
+        .description(description)
+        .timestamp(Timestamp::now())
+        .color(Color::LIGHT_GREY)
+}
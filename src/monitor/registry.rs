@@ -0,0 +1,210 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use poise::serenity_prelude::{json, ChannelId, Timestamp};
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use super::{MonitorService, Shared};
+use crate::{storage, Error};
+
+/// Current layout of `services.json`
+const VERSION: u64 = 2;
+
+#[derive(Serialize)]
+struct Saved<'a> {
+    version: u64,
+    services: &'a [Arc<MonitorService>],
+}
+
+/// The running services, saved to disk whenever they change
+pub struct Registry {
+    path: PathBuf,
+    services: Mutex<Vec<Arc<MonitorService>>>,
+}
+
+impl Registry {
+    /// Load the services saved in `path`
+    ///
+    /// Entries that can't be restored are moved to a `.quarantine.json` file next to it
+    /// rather than preventing the rest from starting.
+    pub async fn load(
+        path: PathBuf,
+        shared: &Shared,
+        token: &CancellationToken,
+    ) -> Result<Self, Error> {
+        let value = match tokio::fs::read(&path).await {
+            // A file this build can't make sense of is set aside like one that isn't JSON
+            Ok(bytes) => match json::from_slice(&bytes)
+                .map_err(Error::from)
+                .and_then(migrate)
+            {
+                Ok(value) => value,
+                Err(err) => {
+                    let mut corrupt = path.as_os_str().to_owned();
+                    corrupt.push(format!(".corrupt-{}", Timestamp::now().unix_timestamp()));
+                    log::error!(
+                        "{} can't be loaded ({err}), moving it to {}",
+                        path.display(),
+                        corrupt.to_string_lossy()
+                    );
+                    tokio::fs::rename(&path, corrupt).await?;
+                    json::Value::Null
+                }
+            },
+            Err(err) if err.kind() == ErrorKind::NotFound => json::Value::Null,
+            Err(err) => return Err(err.into()),
+        };
+
+        let entries = match value {
+            json::Value::Object(mut value) => value.remove("services").unwrap_or_default(),
+            _ => json::Value::Null,
+        };
+        let entries = match entries {
+            json::Value::Array(entries) => entries,
+            _ => Vec::new(),
+        };
+
+        let mut services = Vec::with_capacity(entries.len());
+        let mut quarantined = Vec::new();
+        for entry in entries {
+            let service = MonitorService::from_value(shared, token.child_token(), entry.clone());
+            match service {
+                Ok(service) => services.push(Arc::new(service)),
+                Err(err) => {
+                    log::error!("Quarantining invalid service {entry}: {err}");
+                    quarantined.push(json::json!({
+                        "entry": entry,
+                        "error": err.to_string(),
+                        "quarantined_at": Timestamp::now(),
+                    }));
+                }
+            }
+        }
+
+        if !quarantined.is_empty() {
+            quarantine(&path, quarantined).await?;
+        }
+
+        Ok(Self {
+            path,
+            services: Mutex::new(services),
+        })
+    }
+
+    /// A snapshot of the running services
+    pub async fn services(&self) -> Vec<Arc<MonitorService>> {
+        self.services.lock().await.clone()
+    }
+
+    pub async fn get(&self, channel_id: ChannelId) -> Option<Arc<MonitorService>> {
+        self.services
+            .lock()
+            .await
+            .iter()
+            .find(|service| service.channel_id() == channel_id)
+            .cloned()
+    }
+
+    /// Add a service unless the channel already has one, returning whether it was added
+    pub async fn add(&self, service: Arc<MonitorService>) -> Result<bool, Error> {
+        let mut services = self.services.lock().await;
+        if services
+            .iter()
+            .any(|s| s.channel_id() == service.channel_id())
+        {
+            return Ok(false);
+        }
+        services.push(service);
+        self.save_locked(&services).await?;
+        Ok(true)
+    }
+
+    pub async fn remove(
+        &self,
+        channel_id: ChannelId,
+    ) -> Result<Option<Arc<MonitorService>>, Error> {
+        let mut services = self.services.lock().await;
+        let Some(index) = services.iter().position(|s| s.channel_id() == channel_id) else {
+            return Ok(None);
+        };
+        let service = services.swap_remove(index);
+        self.save_locked(&services).await?;
+        Ok(Some(service))
+    }
+
+    pub async fn save(&self) -> Result<usize, Error> {
+        let services = self.services.lock().await;
+        self.save_locked(&services).await?;
+        Ok(services.len())
+    }
+
+    async fn save_locked(&self, services: &[Arc<MonitorService>]) -> Result<(), Error> {
+        let data = json::to_vec(&Saved {
+            version: VERSION,
+            services,
+        })?;
+        storage::write_atomic(&self.path, &data).await
+    }
+}
+
+/// Bring a saved registry up to [`VERSION`]
+fn migrate(mut value: json::Value) -> Result<json::Value, Error> {
+    loop {
+        let version = match &value {
+            // Before versioning the file was just the list of services
+            json::Value::Array(_) => 1,
+            _ => value["version"]
+                .as_u64()
+                .ok_or("services.json is missing its version")?,
+        };
+
+        value = match version {
+            1 => json::json!({ "version": 2, "services": value }),
+            VERSION => return Ok(value),
+            version => {
+                return Err(format!(
+                    "services.json version {version} is newer than this build supports ({VERSION})"
+                )
+                .into())
+            }
+        };
+
+        log::info!("Migrated services.json from version {version}");
+    }
+}
+
+async fn quarantine(path: &Path, entries: Vec<json::Value>) -> Result<(), Error> {
+    let path = path.with_extension("quarantine.json");
+
+    let mut existing: Vec<json::Value> = match tokio::fs::read(&path).await {
+        Ok(bytes) => json::from_slice(&bytes).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    existing.extend(entries);
+
+    storage::write_atomic(&path, &json::to_vec_pretty(&existing)?).await
+}
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::json;
+
+    use super::{migrate, VERSION};
+
+    #[test]
+    fn migrate_list() {
+        let value = migrate(json::json!([])).unwrap();
+        assert_eq!(value, json::json!({ "version": VERSION, "services": [] }));
+    }
+
+    #[test]
+    fn migrate_rejects_missing_and_newer_versions() {
+        assert!(migrate(json::json!({ "services": [] })).is_err());
+        assert!(migrate(json::json!({ "version": VERSION + 1, "services": [] })).is_err());
+    }
+}
@@ -0,0 +1,492 @@
+use std::path::PathBuf;
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use futures::future::BoxFuture;
+use itertools::Itertools;
+use poise::serenity_prelude::{
+    json, Color, CreateAllowedMentions, CreateAttachment, CreateEmbed, CreateMessage,
+    EditAttachments, EditMessage, Mentionable, MessageId, Timestamp,
+};
+use serde::{Deserialize, Serialize};
+use tokio::time::{self, Duration, Instant};
+
+use super::{Monitor, MonitorContext, MonitorKind};
+use crate::{
+    chat::Text,
+    history::{self, Sample, CHART_FILE},
+    logs::escape_markdown,
+    query::{self, FullStat},
+    server::Server,
+    sessions::format_duration,
+    slp::{self, ServerStatus, Timeouts},
+    storage, uptime, Context, Error,
+};
+
+pub const KIND: MonitorKind = MonitorKind {
+    name: "status",
+    tag: "Status",
+    create,
+    load: |value| Ok(Box::new(json::from_value::<Status>(value)?)),
+};
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+const ICON_FILE: &str = "server-icon.png";
+/// The chart shown under the status, and the one attached next to it, with how far back each
+/// goes in seconds
+const CHARTS: [(&str, i64); 2] = [(CHART_FILE, 24 * 3600), ("players-7d.png", KEEP_SAMPLES)];
+/// How far back the samples kept in memory for the charts go, in seconds
+const KEEP_SAMPLES: i64 = 7 * 86400;
+/// How often the charts are redrawn, rather than on every poll
+const CHART_EVERY: Duration = Duration::from_secs(15 * 60);
+/// Failed pings in a row before the server counts as down
+const OFFLINE_AFTER: u32 = 3;
+/// Embed field values are capped at 1024 characters
+const FIELD_LIMIT: usize = 1024;
+
+/// What the status embed shows, whichever protocol it came from
+#[derive(Default)]
+pub struct Summary {
+    pub motd: Text,
+    pub version: String,
+    pub online: u64,
+    pub max: u64,
+    /// Markdown rendered names of (some of) the players online
+    pub players: Vec<String>,
+    pub latency: Option<Duration>,
+    /// Only known through Query
+    pub map: String,
+    pub game_type: String,
+    pub plugins: Vec<String>,
+}
+
+impl Summary {
+    fn from_slp(status: &ServerStatus) -> Self {
+        let response = &status.response;
+        let (online, max, players) = match &response.players {
+            Some(players) => (
+                players.online,
+                players.max,
+                players
+                    .sample
+                    .iter()
+                    .map(|player| Text::from_legacy(&player.name).markdown())
+                    .collect(),
+            ),
+            None => (0, 0, Vec::new()),
+        };
+        Self {
+            motd: Text::from_json(&response.description),
+            version: Text::from_legacy(&response.version.name).markdown(),
+            online,
+            max,
+            players,
+            latency: status.latency,
+            ..Self::default()
+        }
+    }
+
+    /// Replace the player sample with Query's full list, and add what only Query knows
+    fn add_query(&mut self, stat: FullStat) {
+        self.online = stat.online;
+        self.max = stat.max;
+        self.players = stat
+            .players
+            .iter()
+            .map(|player| Text::from_legacy(player).markdown())
+            .collect();
+        self.map = stat.map;
+        self.game_type = stat.game_type;
+        self.plugins = stat.plugins;
+        if let Some(server_mod) = stat.server_mod {
+            self.plugins.insert(0, server_mod);
+        }
+    }
+
+    /// Forget everything that's only true while the server is up
+    pub fn offline(&mut self) {
+        self.online = 0;
+        self.players.clear();
+        self.latency = None;
+        self.map.clear();
+        self.game_type.clear();
+        self.plugins.clear();
+    }
+
+    /// The status embed, shared by every status monitor
+    pub fn embed(&self, name: &str, is_online: bool) -> CreateEmbed {
+        let (status, color) = if is_online {
+            ("ONLINE", Color::FOOYOO)
+        } else {
+            ("OFFLINE", Color::RED)
+        };
+
+        // Colour only survives in a code block, so only pay for one when it's needed
+        let description = if self.motd.has_color() {
+            self.motd.ansi()
+        } else {
+            self.motd.markdown()
+        };
+        let players = if self.players.is_empty() {
+            "None".to_string()
+        } else {
+            truncated_list(&self.players)
+        };
+        let version = if self.version.is_empty() {
+            "Unknown"
+        } else {
+            &self.version
+        };
+        let latency = self.latency.map_or_else(
+            || "-".to_string(),
+            |latency| format!("{} ms", latency.as_millis()),
+        );
+
+        let mut embed = CreateEmbed::new()
+            .title(name)
+            .description(description)
+            .fields([
+                ("Status", status, true),
+                ("Players", &format!("{}/{}", self.online, self.max), true),
+                ("Version", version, true),
+                ("Latency", &latency, true),
+            ]);
+        if !self.game_type.is_empty() {
+            embed = embed.field("Game Type", &self.game_type, true);
+        }
+        if !self.map.is_empty() {
+            embed = embed.field("Map", escape_markdown(&self.map), true);
+        }
+        if !self.plugins.is_empty() {
+            let plugins = self
+                .plugins
+                .iter()
+                .map(|p| escape_markdown(p))
+                .collect_vec();
+            embed = embed.field("Plugins", truncated_list(&plugins), false);
+        }
+
+        embed
+            .field("Currently Online", players, false)
+            .timestamp(Timestamp::now())
+            .color(color)
+    }
+}
+
+/// Join items with commas, cutting off whatever doesn't fit in an embed field
+fn truncated_list(items: &[String]) -> String {
+    let mut list = String::new();
+    for (i, item) in items.iter().enumerate() {
+        let more = format!(" and {} more", items.len() - i);
+        if list.len() + item.len() + 2 + more.len() > FIELD_LIMIT {
+            list.push_str(&more);
+            break;
+        }
+        if i > 0 {
+            list.push_str(", ");
+        }
+        list.push_str(item);
+    }
+    list
+}
+
+/// Keeps a message updated with the server's status
+#[derive(Deserialize, Serialize)]
+pub struct Status {
+    name: String,
+    host: String,
+    port: u16,
+    /// Get the player list from Query instead, if the server has `enable-query` on
+    #[serde(default)]
+    query_port: Option<u16>,
+    mid: MessageId,
+    /// Where player counts are recorded and charted from, if anywhere
+    #[serde(default)]
+    data_path: Option<PathBuf>,
+}
+
+fn create<'a>(
+    ctx: Context<'a>,
+    server: &'a Server,
+) -> BoxFuture<'a, Result<Box<dyn Monitor>, Error>> {
+    Box::pin(async move {
+        let monitor = Status {
+            name: server.name.clone(),
+            host: server.host.clone(),
+            port: server.port,
+            query_port: server.query_port,
+            mid: ctx.channel_id().say(ctx, "Initializing service").await?.id,
+            data_path: Some(server.data_path.clone()),
+        };
+        Ok(Box::new(monitor) as _)
+    })
+}
+
+impl Monitor for Status {
+    fn serialize(&self) -> Result<json::Value, Error> {
+        Ok(json::to_value(self)?)
+    }
+
+    fn run<'a>(&'a self, ctx: &'a MonitorContext) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(self.run_status(ctx))
+    }
+
+    fn describe(&self) -> String {
+        let query = self
+            .query_port
+            .map_or_else(String::new, |port| format!(", Query on {port}"));
+        format!(
+            "Status of {} ({}:{}{query})",
+            self.name, self.host, self.port
+        )
+    }
+
+    fn stop<'a>(&'a self, ctx: &'a MonitorContext) -> BoxFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            ctx.channel_id
+                .edit_message(
+                    &ctx.http,
+                    self.mid,
+                    EditMessage::new().content("Monitor stopped"),
+                )
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+impl Status {
+    /// Where the server's history is kept, going by the configured server at the same address
+    /// for monitors saved before they recorded anything
+    fn data_path(&self, ctx: &MonitorContext) -> Option<PathBuf> {
+        self.data_path.clone().or_else(|| {
+            ctx.config
+                .get()
+                .servers
+                .iter()
+                .find(|server| server.host == self.host && server.port == self.port)
+                .map(|server| server.data_path.clone())
+        })
+    }
+
+    async fn run_status(&self, ctx: &MonitorContext) -> Result<(), Error> {
+        let (name, host, port, mid) = (&self.name, self.host.as_str(), self.port, self.mid);
+        let data_path = self.data_path(ctx);
+        let data_path = data_path.as_deref();
+
+        let cid = ctx.channel_id;
+
+        let mut summary = Summary::default();
+        let mut prev_favicon = String::new();
+        let mut icon = Vec::new();
+        let mut icon_changed = false;
+        // Only the first status monitor of a server records and alerts, any other just shows
+        // what it sees
+        let claim = data_path.and_then(|data_path| storage::claim("status", data_path));
+        let recording = claim.as_ref().map(storage::Claim::data_path);
+        let alerting = recording.is_some() || data_path.is_none();
+        let mut offline_since = match recording {
+            Some(data_path) => {
+                let interval = ctx.config.get().monitors.status_interval().as_secs() as i64;
+                uptime::track(data_path, interval).await?
+            }
+            None => None,
+        };
+        let mut failures = 0;
+        let mut failing_since = None;
+        let mut heartbeat = Timestamp::now().unix_timestamp();
+        // Kept in memory so the charts don't need the whole history read on every redraw
+        let mut samples = match data_path {
+            Some(data_path) => {
+                history::load(data_path, Timestamp::now().unix_timestamp() - KEEP_SAMPLES).await?
+            }
+            None => Vec::new(),
+        };
+        let mut charted: Option<Instant> = None;
+
+        loop {
+            let mut msg = cid.message(&ctx.http, mid).await?;
+
+            log::info!("Updating status for {}:{}", host, port);
+
+            let is_online = match slp::status(host, port, Timeouts::default()).await {
+                Ok(status) => {
+                    summary = Summary::from_slp(&status);
+
+                    if let Some(query_port) = self.query_port {
+                        // The sample is still better than nothing if Query is off or blocked
+                        match query::full_stat(host, query_port, QUERY_TIMEOUT).await {
+                            Ok(stat) => summary.add_query(stat),
+                            Err(err) => {
+                                log::info!("Query of {}:{} failed: {err}", host, query_port)
+                            }
+                        }
+                    }
+
+                    let favicon = status
+                        .response
+                        .favicon
+                        .as_deref()
+                        .unwrap_or("")
+                        .split_once(',')
+                        .map_or("", |d| d.1);
+                    if favicon != prev_favicon {
+                        prev_favicon = favicon.to_owned();
+                        icon = BASE64_STANDARD.decode(favicon)?;
+                        icon_changed = true;
+                    }
+
+                    true
+                }
+                Err(err) => {
+                    log::info!("{}:{} is offline: {err}", host, port);
+                    summary.offline();
+                    false
+                }
+            };
+
+            let now = Timestamp::now().unix_timestamp();
+            if is_online {
+                failures = 0;
+                failing_since = None;
+            } else {
+                failures += 1;
+                failing_since.get_or_insert(now);
+            }
+            match (is_online, offline_since) {
+                // A single dropped ping is no outage, so it only counts once enough fail in a row
+                (false, None) if failures >= OFFLINE_AFTER => {
+                    let since = failing_since.unwrap_or(now);
+                    offline_since = Some(since);
+                    if let Some(data_path) = recording {
+                        uptime::start(data_path, since, now).await?;
+                        heartbeat = now;
+                    }
+                    if alerting {
+                        self.alert(ctx, false, None).await?;
+                    }
+                }
+                (true, Some(since)) => {
+                    offline_since = None;
+                    if let Some(data_path) = recording {
+                        uptime::end(data_path, now).await?;
+                        heartbeat = now;
+                    }
+                    if alerting {
+                        let outage = Duration::from_secs((now - since).max(0) as u64);
+                        self.alert(ctx, true, Some(outage)).await?;
+                    }
+                }
+                _ => (),
+            }
+            if let Some(data_path) = recording.filter(|_| now - heartbeat >= uptime::HEARTBEAT) {
+                uptime::heartbeat(data_path, now).await?;
+                heartbeat = now;
+            }
+
+            let mut attachments = EditAttachments::new();
+            let kept = msg.attachments.iter().find(|a| a.filename == ICON_FILE);
+            match kept {
+                Some(kept) if !icon_changed => attachments = attachments.keep(kept.id),
+                _ if !icon.is_empty() => {
+                    let attachment = CreateAttachment::bytes(icon.clone(), ICON_FILE);
+                    attachments = attachments.add(attachment);
+                    icon_changed = false;
+                }
+                _ => (),
+            }
+
+            let mut embed = summary
+                .embed(name, is_online)
+                .thumbnail(format!("attachment://{ICON_FILE}"));
+            if let Some(since) = offline_since {
+                embed = embed.field("Offline Since", format!("<t:{since}:R>"), true);
+            }
+            if let Some(data_path) = data_path {
+                let sample = Sample::now(is_online.then_some(summary.online));
+                if recording.is_some() {
+                    history::record(data_path, &sample).await?;
+                }
+                samples.push(sample);
+                let cutoff = now - KEEP_SAMPLES;
+                samples.drain(..samples.partition_point(|sample| sample.time < cutoff));
+
+                let kept: Vec<_> = msg
+                    .attachments
+                    .iter()
+                    .filter(|a| CHARTS.iter().any(|(file, _)| a.filename == *file))
+                    .collect();
+                if kept.len() == CHARTS.len()
+                    && charted.is_some_and(|charted| charted.elapsed() < CHART_EVERY)
+                {
+                    for chart in kept {
+                        attachments = attachments.keep(chart.id);
+                    }
+                } else {
+                    let interval = ctx.config.get().monitors.status_interval().as_secs() as i64;
+                    for (file, range) in CHARTS {
+                        let chart = history::render(&samples, range, interval)?;
+                        attachments = attachments.add(CreateAttachment::bytes(chart, file));
+                    }
+                    charted = Some(Instant::now());
+                }
+                embed = embed.image(format!("attachment://{CHART_FILE}"));
+            }
+
+            msg.edit(
+                &ctx.http,
+                EditMessage::new()
+                    .content("")
+                    .attachments(attachments)
+                    .embed(embed),
+            )
+            .await?;
+
+            log::info!("Updated status for {}:{}", host, port);
+
+            tokio::select! {
+                _ = ctx.token.cancelled() => break,
+                _ = time::sleep(ctx.config.get().monitors.status_interval()) => ()
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Post that the server went down or came back, if outage alerts are on
+    async fn alert(
+        &self,
+        ctx: &MonitorContext,
+        is_online: bool,
+        outage: Option<Duration>,
+    ) -> Result<(), Error> {
+        let monitors = ctx.config.get().monitors;
+        if !monitors.outage_alerts {
+            return Ok(());
+        }
+
+        let mut message = CreateMessage::new();
+        let embed = if is_online {
+            let after = outage.map_or_else(String::new, |outage| {
+                format!(" after {}", format_duration(outage))
+            });
+            CreateEmbed::new()
+                .description(format!("**{}** is back online{after}", self.name))
+                .color(Color::FOOYOO)
+        } else {
+            // Only going down is worth waking anyone up for
+            if let Some(role) = monitors.outage_role {
+                message = message
+                    .content(role.mention().to_string())
+                    .allowed_mentions(CreateAllowedMentions::new().roles([role]));
+            }
+            CreateEmbed::new()
+                .description(format!("**{}** is offline", self.name))
+                .color(Color::RED)
+        };
+
+        ctx.channel_id
+            .send_message(&ctx.http, message.embed(embed.timestamp(Timestamp::now())))
+            .await?;
+        Ok(())
+    }
+}
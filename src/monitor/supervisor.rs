@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use poise::serenity_prelude::{self as serenity, HttpError, StatusCode, Timestamp};
+
+use crate::Error;
+
+const BASE_DELAY: Duration = Duration::from_secs(5);
+const MAX_DELAY: Duration = Duration::from_secs(30 * 60);
+/// A run lasting this long is considered healthy and resets the failure streak
+const HEALTHY_RUN: Duration = Duration::from_secs(10 * 60);
+/// Consecutive failures before the channel is warned
+pub const WARN_AFTER: u32 = 3;
+
+/// Failure bookkeeping for a supervised monitor
+#[derive(Default)]
+pub struct Health {
+    /// Failures since the monitor last ran successfully
+    pub consecutive: u32,
+    /// Failures since the monitor was started
+    pub total: u32,
+    pub last_error: Option<(Timestamp, String)>,
+}
+
+impl Health {
+    /// Record a failed run that began at `started`, returning how long to wait before restarting
+    pub fn record(&mut self, started: Instant, err: &Error) -> Duration {
+        if started.elapsed() >= HEALTHY_RUN {
+            self.consecutive = 0;
+        }
+        self.consecutive += 1;
+        self.total += 1;
+        self.last_error = Some((Timestamp::now(), err.to_string()));
+
+        backoff(self.consecutive)
+    }
+}
+
+/// Exponential backoff with jitter, so monitors that failed together don't retry together
+fn backoff(attempt: u32) -> Duration {
+    let delay = BASE_DELAY
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(MAX_DELAY);
+
+    // Doesn't need to be good randomness, just different between monitors
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    let jitter = 0.5 + (nanos % 1000) as f64 / 2000.0;
+
+    delay.mul_f64(jitter)
+}
+
+/// Whether retrying after `err` is pointless, e.g. because the channel or message is gone
+pub fn is_fatal(err: &Error) -> bool {
+    match err.downcast_ref::<serenity::Error>() {
+        Some(serenity::Error::Http(HttpError::UnsuccessfulRequest(res))) => matches!(
+            res.status_code,
+            StatusCode::FORBIDDEN | StatusCode::NOT_FOUND | StatusCode::UNAUTHORIZED
+        ),
+        _ => false,
+    }
+}
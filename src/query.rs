@@ -0,0 +1,154 @@
+//! Client for the GameSpy4 based Query protocol, enabled with `enable-query` in
+//! `server.properties`
+
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{
+    net::UdpSocket,
+    time::{self, Duration},
+};
+
+use crate::Error;
+
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+const HANDSHAKE: u8 = 0x09;
+const STAT: u8 = 0x00;
+// Precedes the key-value section, meant for splitting the response across packets,
+// which no server actually does
+const KV_PADDING: &[u8] = b"splitnum\0\x80\0";
+const PLAYER_PADDING: &[u8] = b"\x01player_\0\0";
+
+/// What a full stat reports beyond what a status response already does
+pub struct FullStat {
+    pub game_type: String,
+    /// The server software, e.g. `Paper on Bukkit 1.20.4`, if it reports one
+    pub server_mod: Option<String>,
+    pub plugins: Vec<String>,
+    pub map: String,
+    pub online: u64,
+    pub max: u64,
+    /// Every player online, unlike the sample in a status response
+    pub players: Vec<String>,
+}
+
+/// Request a full stat, waiting up to `timeout` for each response
+pub async fn full_stat(host: &str, port: u16, timeout: Duration) -> Result<FullStat, Error> {
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).await?;
+    socket.connect((host, port)).await?;
+
+    // Only the lower 4 bits of each byte are used
+    let session = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(1, |time| time.subsec_nanos() as i32)
+        & 0x0F0F0F0F;
+    let mut buf = [0; 8192];
+
+    let mut handshake = Vec::with_capacity(7);
+    handshake.extend_from_slice(&MAGIC);
+    handshake.push(HANDSHAKE);
+    handshake.extend_from_slice(&session.to_be_bytes());
+    let response = request(&socket, &handshake, &mut buf, timeout).await?;
+    let token = parse_header(response, HANDSHAKE, session)?;
+    // The challenge token is sent as a decimal string, but has to be sent back as an integer
+    let token: i32 = std::str::from_utf8(token)?
+        .trim_end_matches('\0')
+        .parse()
+        .map_err(|_| invalid("Malformed challenge token"))?;
+
+    let mut stat = Vec::with_capacity(15);
+    stat.extend_from_slice(&MAGIC);
+    stat.push(STAT);
+    stat.extend_from_slice(&session.to_be_bytes());
+    stat.extend_from_slice(&token.to_be_bytes());
+    // Padding is what makes this a full stat rather than a basic one
+    stat.extend_from_slice(&[0; 4]);
+    let response = request(&socket, &stat, &mut buf, timeout).await?;
+    let body = parse_header(response, STAT, session)?;
+
+    parse_full_stat(body)
+}
+
+async fn request<'a>(
+    socket: &UdpSocket,
+    packet: &[u8],
+    buf: &'a mut [u8],
+    timeout: Duration,
+) -> Result<&'a [u8], Error> {
+    socket.send(packet).await?;
+    let len = time::timeout(timeout, socket.recv(buf))
+        .await
+        .map_err(|_| io::Error::new(ErrorKind::TimedOut, "Server did not answer query"))??;
+    Ok(&buf[..len])
+}
+
+/// Check the type and session of a response, returning the rest
+fn parse_header(response: &[u8], kind: u8, session: i32) -> Result<&[u8], Error> {
+    match response {
+        [k, rest @ ..] if *k == kind && rest.get(..4) == Some(&session.to_be_bytes()[..]) => {
+            Ok(&rest[4..])
+        }
+        _ => Err(invalid("Response does not match request")),
+    }
+}
+
+fn parse_full_stat(body: &[u8]) -> Result<FullStat, Error> {
+    let body = body
+        .strip_prefix(KV_PADDING)
+        .ok_or_else(|| invalid("Missing key-value section"))?;
+
+    // Strings are null terminated, and an empty one ends each section
+    let mut strings = body.split(|&b| b == 0).map(String::from_utf8_lossy);
+
+    let mut values = HashMap::new();
+    loop {
+        let key = strings
+            .next()
+            .ok_or_else(|| invalid("Truncated key-value section"))?;
+        if key.is_empty() {
+            break;
+        }
+        let value = strings.next().unwrap_or_default();
+        values.insert(key.into_owned(), value.into_owned());
+    }
+
+    let rest = body
+        .windows(PLAYER_PADDING.len())
+        .position(|window| window == PLAYER_PADDING)
+        .map_or(&[][..], |i| &body[i + PLAYER_PADDING.len()..]);
+    let players = rest
+        .split(|&b| b == 0)
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .take_while(|name| !name.is_empty())
+        .collect();
+
+    let mut value = |key: &str| values.remove(key).unwrap_or_default();
+
+    // `<server mod>: <plugin>; <plugin>`, or just the server mod if there are no plugins
+    let plugins = value("plugins");
+    let (server_mod, plugins) = match plugins.split_once(": ") {
+        Some((server_mod, plugins)) => (
+            Some(server_mod.to_owned()),
+            plugins.split("; ").map(str::to_owned).collect(),
+        ),
+        None if plugins.is_empty() => (None, Vec::new()),
+        None => (Some(plugins), Vec::new()),
+    };
+
+    Ok(FullStat {
+        game_type: value("gametype"),
+        server_mod,
+        plugins,
+        map: value("map"),
+        online: value("numplayers").parse().unwrap_or(0),
+        max: value("maxplayers").parse().unwrap_or(0),
+        players,
+    })
+}
+
+fn invalid(msg: &str) -> Error {
+    Box::new(io::Error::new(ErrorKind::InvalidData, msg.to_owned()))
+}
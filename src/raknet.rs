@@ -0,0 +1,112 @@
+//! Bedrock Edition server status through RakNet's unconnected ping
+
+use std::{
+    io::{self, ErrorKind},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{
+    net::{lookup_host, UdpSocket},
+    time::{self, Duration, Instant},
+};
+
+use crate::Error;
+
+const UNCONNECTED_PING: u8 = 0x01;
+const UNCONNECTED_PONG: u8 = 0x1C;
+/// Marks offline (unconnected) RakNet messages
+const MAGIC: [u8; 16] = [
+    0x00, 0xFF, 0xFF, 0x00, 0xFE, 0xFE, 0xFE, 0xFE, 0xFD, 0xFD, 0xFD, 0xFD, 0x12, 0x34, 0x56, 0x78,
+];
+const ATTEMPTS: u32 = 3;
+
+/// The server's advertisement from an unconnected pong
+pub struct BedrockStatus {
+    /// The first MOTD line, which is what clients show in the server list
+    pub motd: String,
+    /// The second MOTD line, usually the world name
+    pub sub_motd: String,
+    pub version: String,
+    pub online: u64,
+    pub max: u64,
+    pub game_mode: String,
+    pub latency: Duration,
+}
+
+/// Ping a Bedrock server, waiting up to `timeout` for each of a few attempts
+pub async fn status(host: &str, port: u16, timeout: Duration) -> Result<BedrockStatus, Error> {
+    let addr = lookup_host((host, port))
+        .await?
+        .next()
+        .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "Host did not resolve"))?;
+    let local = if addr.is_ipv4() { "0.0.0.0" } else { "::" };
+    let socket = UdpSocket::bind((local, 0)).await?;
+    socket.connect(addr).await?;
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis() as i64);
+    let guid = time.rotate_left(17) ^ i64::from(std::process::id());
+
+    let mut ping = Vec::with_capacity(33);
+    ping.push(UNCONNECTED_PING);
+    ping.extend_from_slice(&time.to_be_bytes());
+    ping.extend_from_slice(&MAGIC);
+    ping.extend_from_slice(&guid.to_be_bytes());
+
+    let mut buf = [0; 1500];
+    // It's UDP, so give a lost packet a couple more chances
+    for _ in 0..ATTEMPTS {
+        let start = Instant::now();
+        socket.send(&ping).await?;
+
+        while let Ok(len) = time::timeout_at(start + timeout, socket.recv(&mut buf)).await {
+            let pong = &buf[..len?];
+            // Anything else that turns up, like garbage or someone else's pong, isn't the answer
+            if is_pong_to(pong, time) {
+                return parse_pong(pong, start.elapsed());
+            }
+        }
+    }
+
+    Err(Box::new(io::Error::new(
+        ErrorKind::TimedOut,
+        "Server did not answer ping",
+    )))
+}
+
+/// Whether `pong` is an unconnected pong answering the ping sent at `time`
+fn is_pong_to(pong: &[u8], time: i64) -> bool {
+    // id, time, server guid, magic, string length
+    pong.len() >= 35
+        && pong[0] == UNCONNECTED_PONG
+        && pong[1..9] == time.to_be_bytes()
+        && pong[17..33] == MAGIC
+}
+
+fn parse_pong(pong: &[u8], latency: Duration) -> Result<BedrockStatus, Error> {
+    let invalid = |msg| Box::new(io::Error::new(ErrorKind::InvalidData, msg));
+
+    let len = u16::from_be_bytes([pong[33], pong[34]]) as usize;
+    let advertisement = pong
+        .get(35..35 + len)
+        .ok_or_else(|| invalid("Advertisement is longer than the pong"))?;
+    let advertisement = String::from_utf8_lossy(advertisement);
+
+    // MCPE;motd;protocol;version;online;max;server id;sub motd;game mode;...
+    let fields: Vec<&str> = advertisement.split(';').collect();
+    if fields.len() < 6 {
+        return Err(invalid("Advertisement is missing fields"));
+    }
+    let field = |i: usize| fields.get(i).copied().unwrap_or_default().to_owned();
+
+    Ok(BedrockStatus {
+        motd: field(1),
+        sub_motd: field(7),
+        version: field(3),
+        online: fields[4].parse().unwrap_or(0),
+        max: fields[5].parse().unwrap_or(0),
+        game_mode: field(8),
+        latency,
+    })
+}
@@ -0,0 +1,161 @@
+use std::{fmt, io, mem};
+
+use bytes::{Buf, BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Longest payload a server sends in one packet, longer responses are split
+///
+/// Vanilla splits responses every 4096 UTF-16 code units and only then encodes them as UTF-8,
+/// which takes up to three bytes for each.
+const MAX_RESPONSE: usize = 3 * 4096;
+/// Longest payload a vanilla server accepts, it reads requests into a 1460 byte buffer
+pub const MAX_REQUEST: usize = 1446;
+/// Request id and type, plus the payload's terminator and the empty string after it
+const HEADER_LEN: usize = mem::size_of::<i32>() * 2 + 2;
+
+#[derive(Debug)]
+pub enum RconError {
+    /// The server rejected the password
+    AuthFailed,
+    /// The server didn't respond in time
+    Timeout,
+    /// The connection closed, or couldn't be made
+    Disconnected,
+    /// The server sent something that isn't RCON
+    Malformed(String),
+    /// A request longer than the server would accept
+    PayloadTooLarge(usize),
+    Io(io::Error),
+}
+
+impl fmt::Display for RconError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AuthFailed => write!(f, "RCON password was rejected"),
+            Self::Timeout => write!(f, "Server took too long to respond"),
+            Self::Disconnected => write!(f, "Server unreachable"),
+            Self::Malformed(msg) => write!(f, "Malformed RCON packet: {msg}"),
+            Self::PayloadTooLarge(len) => write!(
+                f,
+                "Command is {len} bytes long, the server only accepts up to {MAX_REQUEST}"
+            ),
+            Self::Io(err) => write!(f, "RCON connection failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for RconError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RconError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe => Self::Disconnected,
+            _ => Self::Io(err),
+        }
+    }
+}
+
+pub struct Packet {
+    pub req_id: i32,
+    pub ptype: i32,
+    pub payload: Vec<u8>,
+}
+
+/// Frames RCON packets, a little endian length followed by that many bytes
+pub struct RconCodec;
+
+impl Decoder for RconCodec {
+    type Item = Packet;
+    type Error = RconError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Packet>, RconError> {
+        let Some(length) = src.get(..4) else {
+            return Ok(None);
+        };
+        let length = i32::from_le_bytes(length.try_into().unwrap());
+        let length = usize::try_from(length)
+            .ok()
+            .filter(|length| (HEADER_LEN..=HEADER_LEN + MAX_RESPONSE).contains(length))
+            .ok_or_else(|| RconError::Malformed(format!("invalid packet length {length}")))?;
+
+        if src.len() < 4 + length {
+            src.reserve(4 + length - src.len());
+            return Ok(None);
+        }
+
+        src.advance(4);
+        let req_id = src.get_i32_le();
+        let ptype = src.get_i32_le();
+        let payload = src.split_to(length - HEADER_LEN).to_vec();
+        src.advance(2);
+
+        Ok(Some(Packet {
+            req_id,
+            ptype,
+            payload,
+        }))
+    }
+}
+
+impl Encoder<Packet> for RconCodec {
+    type Error = RconError;
+
+    fn encode(&mut self, packet: Packet, dst: &mut BytesMut) -> Result<(), RconError> {
+        if packet.payload.len() > MAX_REQUEST {
+            return Err(RconError::PayloadTooLarge(packet.payload.len()));
+        }
+
+        dst.reserve(4 + HEADER_LEN + packet.payload.len());
+        dst.put_i32_le((HEADER_LEN + packet.payload.len()) as i32);
+        dst.put_i32_le(packet.req_id);
+        dst.put_i32_le(packet.ptype);
+        dst.put_slice(&packet.payload);
+        dst.put_slice(&[0, 0]);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{BufMut, BytesMut};
+    use tokio_util::codec::Decoder;
+
+    use super::{RconCodec, HEADER_LEN};
+
+    fn frame(payload: &[u8]) -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.put_i32_le((HEADER_LEN + payload.len()) as i32);
+        buf.put_i32_le(2);
+        buf.put_i32_le(0);
+        buf.put_slice(payload);
+        buf.put_slice(&[0, 0]);
+        buf
+    }
+
+    #[test]
+    fn decodes_full_fragment_of_multibyte_text() {
+        // 4096 characters that each take three bytes
+        let payload = "\u{2603}".repeat(4096);
+        let mut buf = frame(payload.as_bytes());
+        let packet = RconCodec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(packet.req_id, 2);
+        assert_eq!(packet.payload, payload.as_bytes());
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn rejects_oversized_frame() {
+        let mut buf = frame(&vec![b'a'; 3 * 4096 + 1]);
+        assert!(RconCodec.decode(&mut buf).is_err());
+    }
+}
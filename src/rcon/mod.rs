@@ -0,0 +1,774 @@
+use futures::{
+    stream::{SplitSink, SplitStream},
+    SinkExt, StreamExt,
+};
+use poise::serenity_prelude::{json, CreateAttachment, CreateEmbed, Mentionable, Timestamp};
+use std::{
+    collections::HashMap,
+    io::{self, ErrorKind},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc,
+    },
+};
+use tokio::{
+    net::{lookup_host, TcpSocket, TcpStream, ToSocketAddrs},
+    sync::{oneshot, Mutex},
+    task::JoinHandle,
+    time::{self, Duration, Instant},
+};
+use tokio_util::codec::Framed;
+
+use crate::{
+    audit, bans::TempBan, config, logs::is_player_name, server, util::parse_duration, Context,
+    Error,
+};
+
+mod codec;
+
+pub use codec::RconError;
+use codec::{Packet, RconCodec};
+
+/// Longest message Discord accepts, longer responses are sent as a file instead
+const MAX_MESSAGE: usize = 2000;
+/// Embed descriptions are capped at 4096 characters
+const MAX_DESCRIPTION: usize = 4096;
+
+const RESPONSE_VALUE: i32 = 0;
+const EXEC_COMMAND: i32 = 2;
+const AUTH: i32 = 3;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// Temporary bans can't outlast this, in seconds, to keep their end a sensible date
+const MAX_BAN: i64 = 10 * 365 * 86400;
+
+type Connection = Framed<TcpStream, RconCodec>;
+
+/// An RCON connection that is only made when needed, and remade when it breaks
+pub struct RconManager {
+    host: String,
+    port: u16,
+    password: String,
+    state: Mutex<ConnectionState>,
+}
+
+#[derive(Default)]
+struct ConnectionState {
+    client: Option<Arc<RconClient>>,
+    /// Failed connection attempts since the last successful one
+    failures: u32,
+    /// When connecting may be tried again
+    retry_at: Option<Instant>,
+}
+
+impl RconManager {
+    pub fn new(host: String, port: u16, password: String) -> Self {
+        Self {
+            host,
+            port,
+            password,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Whether `other` connects to the same place with the same password
+    pub fn same_settings(&self, other: &Self) -> bool {
+        self.host == other.host && self.port == other.port && self.password == other.password
+    }
+
+    /// Run a command, connecting first if there is no working connection
+    pub async fn send_command(&self, command: &str) -> Result<String, RconError> {
+        let client = {
+            let mut state = self.state.lock().await;
+
+            // A restarted server closes the connection, which only shows up once it's read from
+            if state
+                .client
+                .as_ref()
+                .is_some_and(|client| client.is_closed())
+            {
+                log::info!("RCON connection to {}:{} was closed", self.host, self.port);
+                state.client = None;
+            }
+
+            match &state.client {
+                Some(client) => client.clone(),
+                None => {
+                    let client = Arc::new(self.connect(&mut state).await?);
+                    state.client.insert(client).clone()
+                }
+            }
+        };
+
+        match client.send_command(command).await {
+            Ok(response) => Ok(response),
+            // A slow command doesn't mean the connection is broken, and a long one never got sent
+            Err(err @ (RconError::Timeout | RconError::PayloadTooLarge(_))) => Err(err),
+            Err(err) => {
+                log::warn!("RCON command failed, dropping connection: {err}");
+                let mut state = self.state.lock().await;
+                if state
+                    .client
+                    .as_ref()
+                    .is_some_and(|current| Arc::ptr_eq(current, &client))
+                {
+                    state.client = None;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    async fn connect(&self, state: &mut ConnectionState) -> Result<RconClient, RconError> {
+        if state
+            .retry_at
+            .is_some_and(|retry_at| Instant::now() < retry_at)
+        {
+            return Err(RconError::Disconnected);
+        }
+
+        let addr = (self.host.as_str(), self.port);
+        let result = time::timeout(CONNECT_TIMEOUT, RconClient::connect(addr, &self.password))
+            .await
+            .unwrap_or(Err(RconError::Timeout));
+
+        match result {
+            Ok(client) => {
+                log::info!("Connected to RCON at {}:{}", self.host, self.port);
+                state.failures = 0;
+                state.retry_at = None;
+                Ok(client)
+            }
+            Err(err) => {
+                state.failures += 1;
+                let delay = RECONNECT_DELAY
+                    .saturating_mul(1 << (state.failures - 1).min(16))
+                    .min(MAX_RECONNECT_DELAY);
+                state.retry_at = Some(Instant::now() + delay);
+                log::warn!(
+                    "Unable to connect to RCON at {}:{} ({err}), retrying in {}s at the earliest",
+                    self.host,
+                    self.port,
+                    delay.as_secs()
+                );
+
+                // Wrong passwords are worth telling apart from a server that's down
+                match err {
+                    RconError::AuthFailed => Err(err),
+                    _ => Err(RconError::Disconnected),
+                }
+            }
+        }
+    }
+}
+
+/// A response still being received, by the id of the command it answers
+type Pending = Arc<std::sync::Mutex<HashMap<i32, PendingResponse>>>;
+
+/// An authenticated connection, which can have several commands in flight at once
+///
+/// Responses are read by a background task and handed to whichever request they answer.
+pub struct RconClient {
+    writer: Mutex<SplitSink<Connection, Packet>>,
+    req_id: AtomicI32,
+    pending: Pending,
+    reader: JoinHandle<()>,
+}
+
+struct PendingResponse {
+    response: Vec<u8>,
+    done: oneshot::Sender<Vec<u8>>,
+}
+
+impl RconClient {
+    pub async fn connect<A: ToSocketAddrs>(addr: A, password: &str) -> Result<Self, RconError> {
+        let mut connection = Framed::new(connect_any(addr).await?, RconCodec);
+
+        // Ids come in pairs, an even one for the request and the odd one after it for its sentinel
+        authenticate(&mut connection, 0, password).await?;
+        let (writer, reader) = connection.split();
+
+        let pending = Pending::default();
+        let reader = tokio::spawn(read_responses(reader, pending.clone()));
+
+        Ok(Self {
+            writer: Mutex::new(writer),
+            req_id: AtomicI32::new(2),
+            pending,
+            reader,
+        })
+    }
+
+    /// Whether the connection has closed, or stopped making sense
+    fn is_closed(&self) -> bool {
+        self.reader.is_finished()
+    }
+
+    /// Run a command, returning its whole response
+    pub async fn send_command(&self, command: &str) -> Result<String, RconError> {
+        let req_id = self.req_id.fetch_add(2, Ordering::Relaxed);
+        let (done, response) = oneshot::channel();
+        self.pending.lock().unwrap().insert(
+            req_id,
+            PendingResponse {
+                response: Vec::new(),
+                done,
+            },
+        );
+
+        let result = self.request(req_id, command, response).await;
+        if result.is_err() {
+            self.pending.lock().unwrap().remove(&req_id);
+        }
+
+        // A fragment can end in the middle of a character, so only decode the whole thing, and
+        // some plugins don't bother with UTF-8 at all
+        Ok(String::from_utf8_lossy(&result?).into_owned())
+    }
+
+    async fn request(
+        &self,
+        req_id: i32,
+        command: &str,
+        response: oneshot::Receiver<Vec<u8>>,
+    ) -> Result<Vec<u8>, RconError> {
+        {
+            // Long responses are split across packets with nothing marking the last one, but
+            // requests are answered in order, so the response to a request the server doesn't
+            // understand comes right after the last part
+            let mut writer = self.writer.lock().await;
+            writer
+                .feed(Packet {
+                    req_id,
+                    ptype: EXEC_COMMAND,
+                    payload: command.as_bytes().to_vec(),
+                })
+                .await?;
+            writer
+                .feed(Packet {
+                    req_id: req_id + 1,
+                    ptype: RESPONSE_VALUE,
+                    payload: Vec::new(),
+                })
+                .await?;
+            writer.flush().await?;
+        }
+
+        match time::timeout(REQUEST_TIMEOUT, response).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(RconError::Disconnected),
+            Err(_) => Err(RconError::Timeout),
+        }
+    }
+}
+
+impl Drop for RconClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Connect to each address `addr` resolves to in turn, until one accepts
+async fn connect_any<A: ToSocketAddrs>(addr: A) -> Result<TcpStream, RconError> {
+    let mut last_err = None;
+    for addr in lookup_host(addr).await? {
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        socket.set_keepalive(true)?;
+        match socket.connect(addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    let err = last_err.unwrap_or_else(|| {
+        io::Error::new(ErrorKind::InvalidInput, "could not resolve to any address")
+    });
+    Err(err.into())
+}
+
+async fn authenticate(
+    connection: &mut Connection,
+    req_id: i32,
+    password: &str,
+) -> Result<(), RconError> {
+    connection
+        .send(Packet {
+            req_id,
+            ptype: AUTH,
+            payload: password.as_bytes().to_vec(),
+        })
+        .await?;
+    loop {
+        let packet = connection.next().await.ok_or(RconError::Disconnected)??;
+        match (packet.req_id, packet.ptype) {
+            (-1, _) => return Err(RconError::AuthFailed),
+            // Some servers send an empty response ahead of the auth response
+            (_, RESPONSE_VALUE) => continue,
+            (id, _) if id == req_id => return Ok(()),
+            (id, _) => {
+                return Err(RconError::Malformed(format!(
+                    "expected a response to {req_id}, got {id}"
+                )))
+            }
+        }
+    }
+}
+
+/// Hand responses to their requests until the connection closes
+async fn read_responses(mut reader: SplitStream<Connection>, pending: Pending) {
+    loop {
+        let packet = match reader.next().await {
+            Some(Ok(packet)) => packet,
+            Some(Err(err)) => {
+                log::info!("Stopped reading RCON responses: {err}");
+                break;
+            }
+            None => {
+                log::info!("RCON connection closed");
+                break;
+            }
+        };
+
+        let id = packet.req_id;
+        let mut pending = pending.lock().unwrap();
+        if id % 2 == 0 {
+            if let Some(request) = pending.get_mut(&id) {
+                request.response.extend_from_slice(&packet.payload);
+            }
+        } else if let Some(request) = pending.remove(&(id - 1)) {
+            // The caller may have given up waiting, which is fine
+            let _ = request.done.send(request.response);
+        }
+        // Anything else answers a request that already timed out
+    }
+
+    // Dropping the senders lets everyone still waiting know the connection is gone
+    pending.lock().unwrap().clear();
+}
+
+/// Run a command on `server`, logging what it said
+pub async fn run_command(server: &server::Server, command: &str) -> Result<String, Error> {
+    let response = server.rcon()?.send_command(command).await?;
+    log::info!("Ran {command:?} on {}: {response:?}", server.id);
+    Ok(response)
+}
+
+pub async fn do_command(
+    ctx: Context<'_>,
+    server: Option<&str>,
+    command: String,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    let config = ctx.data().config.get();
+    let response = run_command(config.servers.get(server)?, &command).await?;
+    ctx.send(response_reply(response).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// A reply showing what a command said
+fn response_reply(response: String) -> poise::CreateReply {
+    if response.is_empty() {
+        poise::CreateReply::default().content("Executed command.")
+    } else if response.len() > MAX_MESSAGE {
+        poise::CreateReply::default().attachment(CreateAttachment::bytes(
+            response.into_bytes(),
+            "response.txt",
+        ))
+    } else {
+        poise::CreateReply::default().content(response)
+    }
+}
+
+/// Run an arbitrary server command. Long responses are sent as a file
+#[poise::command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "config::check_rcon"
+)]
+pub async fn command(
+    ctx: Context<'_>,
+    command: String,
+    #[description = "Defaults to the first server"]
+    #[autocomplete = "server::autocomplete"]
+    server: Option<String>,
+) -> Result<(), Error> {
+    do_command(ctx, server.as_deref(), command).await
+}
+
+#[poise::command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "config::check_rcon"
+)]
+pub async fn say(
+    ctx: Context<'_>,
+    message: String,
+    #[description = "Defaults to the first server"]
+    #[autocomplete = "server::autocomplete"]
+    server: Option<String>,
+) -> Result<(), Error> {
+    do_command(ctx, server.as_deref(), format!("say \"{message}\"")).await
+}
+
+#[poise::command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "config::check_rcon",
+    subcommands("whitelist::add", "whitelist::remove"),
+    subcommand_required
+)]
+pub async fn whitelist(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+pub mod whitelist {
+    use crate::rcon::do_command;
+    use crate::{server, Context, Error};
+
+    /// Adds player profile(s) into the whitelist. The player does not need to be online.
+    #[poise::command(slash_command)]
+    pub async fn add(
+        ctx: Context<'_>,
+        #[description = "Players, or mentions of people who have linked their account"]
+        targets: Vec<String>,
+        #[description = "Defaults to the first server"]
+        #[autocomplete = "server::autocomplete"]
+        server: Option<String>,
+    ) -> Result<(), Error> {
+        let targets = ctx.data().links.resolve(&targets).await?.join(" ");
+        do_command(ctx, server.as_deref(), format!("whitelist add {targets}")).await
+    }
+
+    /// Removes player profile(s) from the whitelist. The player does not need to be online.
+    #[poise::command(slash_command)]
+    pub async fn remove(
+        ctx: Context<'_>,
+        #[description = "Players, or mentions of people who have linked their account"]
+        targets: Vec<String>,
+        #[description = "Defaults to the first server"]
+        #[autocomplete = "server::autocomplete"]
+        server: Option<String>,
+    ) -> Result<(), Error> {
+        let targets = ctx.data().links.resolve(&targets).await?.join(" ");
+        do_command(
+            ctx,
+            server.as_deref(),
+            format!("whitelist remove {targets}"),
+        )
+        .await
+    }
+}
+
+/// `target` with mentions swapped for linked names, as long as it's a valid player name
+async fn resolve_player(ctx: Context<'_>, target: &str) -> Result<String, Error> {
+    let name = ctx
+        .data()
+        .links
+        .resolve(&[target.to_owned()])
+        .await?
+        .remove(0);
+    if !is_player_name(&name) {
+        return Err(format!("{name} isn't a valid Minecraft username").into());
+    }
+    Ok(name)
+}
+
+/// Run a moderation command on `server` and record who ran it
+async fn moderate(
+    ctx: Context<'_>,
+    server: &server::Server,
+    action: &str,
+    command: String,
+    mut details: json::Value,
+) -> Result<String, Error> {
+    ctx.defer_ephemeral().await?;
+    let response = run_command(server, &command).await?;
+
+    details["server"] = server.id.clone().into();
+    details["response"] = response.clone().into();
+    audit::record(&ctx.data().data_path, ctx.author().id, action, details).await?;
+    Ok(response)
+}
+
+/// `reason`, saying when the ban ends if it does
+fn ban_reason(reason: Option<&str>, until: Option<i64>) -> String {
+    let until = until
+        .and_then(|until| Timestamp::from_unix_timestamp(until).ok())
+        // Like 2030-01-01T12:00, which reads fine and doesn't need a time zone library
+        .map(|until| format!("(until {} UTC)", &until.to_string()[..16]));
+    [reason.map(str::to_owned), until]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The address in a ban-ip response like `Banned IP 1.2.3.4: Griefing`
+fn banned_ip(response: &str) -> Option<String> {
+    let address = response
+        .strip_prefix("Banned IP ")?
+        .split_whitespace()
+        .next()?;
+    let ip: IpAddr = address.trim_end_matches(':').parse().ok()?;
+    Some(ip.to_string())
+}
+
+/// Ban a player name or IP address, for good or until `duration` is up
+async fn ban_target(
+    ctx: Context<'_>,
+    server: Option<&str>,
+    target: String,
+    ip: bool,
+    duration: Option<String>,
+    reason: Option<String>,
+) -> Result<(), Error> {
+    let until = match duration {
+        Some(duration) => {
+            let seconds = parse_duration(&duration)?;
+            if seconds > MAX_BAN {
+                return Err(
+                    "Bans can last 10 years at most, leave out the duration to ban for good".into(),
+                );
+            }
+            let until = Timestamp::now().unix_timestamp().checked_add(seconds);
+            Some(until.ok_or("That ban would end too far in the future")?)
+        }
+        None => None,
+    };
+    let config = ctx.data().config.get();
+    let server = config.servers.get(server)?;
+
+    let action = if ip { "ban-ip" } else { "ban" };
+    let command = format!("{action} {target} {}", ban_reason(reason.as_deref(), until));
+    let details = json::json!({ "target": target, "reason": reason, "until": until });
+    let mut response =
+        moderate(ctx, server, action, command.trim_end().to_owned(), details).await?;
+
+    let bans = &ctx.data().bans;
+    // Banning the IP of a player bans the address they're connected from, which is what has to
+    // be pardoned, and what temporary bans are kept under
+    let address = banned_ip(&response);
+    let stored = if ip {
+        address.clone().unwrap_or_else(|| target.clone())
+    } else {
+        target.clone()
+    };
+    // Someone who's already banned stays banned as they were, which only the end of a
+    // temporary ban can be changed on
+    let unchanged = response.starts_with("Nothing changed");
+    match until {
+        Some(until) if response.starts_with("Banned") => {
+            if ip && address.is_none() {
+                return Err(format!(
+                    "{response}\nThe address isn't in the response, so this ban is for good"
+                )
+                .into());
+            }
+            bans.add(TempBan {
+                server: server.id.clone(),
+                target: stored,
+                ip,
+                until,
+                moderator: ctx.author().id,
+                reason,
+            })
+            .await?
+        }
+        Some(until) if unchanged => {
+            if bans.renew(&server.id, &stored, ip, until).await? {
+                response.push_str(&format!("\nThe temporary ban now ends <t:{until}:f>"));
+            }
+        }
+        // Anything else, like an unknown player, banned no one
+        Some(_) => (),
+        None => {
+            if bans.remove(&server.id, &stored, ip).await? && unchanged {
+                response.push_str("\nThe temporary ban is now for good");
+            }
+        }
+    }
+
+    ctx.send(response_reply(response).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Ban a player, for good or for a while
+#[poise::command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "config::check_rcon"
+)]
+pub async fn ban(
+    ctx: Context<'_>,
+    #[description = "A player, or a mention of someone who has linked their account"]
+    player: String,
+    #[description = "How long for, like 12h, 7d or 2w (default forever)"] duration: Option<String>,
+    #[description = "Shown to the player"] reason: Option<String>,
+    #[description = "Defaults to the first server"]
+    #[autocomplete = "server::autocomplete"]
+    server: Option<String>,
+) -> Result<(), Error> {
+    let player = resolve_player(ctx, &player).await?;
+    ban_target(ctx, server.as_deref(), player, false, duration, reason).await
+}
+
+/// Ban an IP address, or the address an online player is connected from
+#[poise::command(
+    rename = "ban-ip",
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "config::check_rcon"
+)]
+pub async fn ban_ip(
+    ctx: Context<'_>,
+    #[description = "An IP address, or a player who is online"] target: String,
+    #[description = "How long for, like 12h, 7d or 2w (default forever)"] duration: Option<String>,
+    #[description = "Shown to the player"] reason: Option<String>,
+    #[description = "Defaults to the first server"]
+    #[autocomplete = "server::autocomplete"]
+    server: Option<String>,
+) -> Result<(), Error> {
+    let target = match target.trim().parse::<IpAddr>() {
+        Ok(ip) => ip.to_string(),
+        Err(_) => resolve_player(ctx, target.trim()).await?,
+    };
+    ban_target(ctx, server.as_deref(), target, true, duration, reason).await
+}
+
+/// Lift the ban on a player or IP address
+#[poise::command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "config::check_rcon"
+)]
+pub async fn pardon(
+    ctx: Context<'_>,
+    #[description = "A player, an IP address, or a mention of someone who has linked"]
+    target: String,
+    #[description = "Defaults to the first server"]
+    #[autocomplete = "server::autocomplete"]
+    server: Option<String>,
+) -> Result<(), Error> {
+    let (target, ip) = match target.trim().parse::<IpAddr>() {
+        Ok(ip) => (ip.to_string(), true),
+        Err(_) => (resolve_player(ctx, target.trim()).await?, false),
+    };
+    let config = ctx.data().config.get();
+    let server = config.servers.get(server.as_deref())?;
+
+    let action = if ip { "pardon-ip" } else { "pardon" };
+    let details = json::json!({ "target": target });
+    let response = moderate(ctx, server, action, format!("{action} {target}"), details).await?;
+    ctx.data().bans.remove(&server.id, &target, ip).await?;
+
+    ctx.send(response_reply(response).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Kick a player off the server
+#[poise::command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "config::check_rcon"
+)]
+pub async fn kick(
+    ctx: Context<'_>,
+    #[description = "A player, or a mention of someone who has linked their account"]
+    player: String,
+    #[description = "Shown to the player"] reason: Option<String>,
+    #[description = "Defaults to the first server"]
+    #[autocomplete = "server::autocomplete"]
+    server: Option<String>,
+) -> Result<(), Error> {
+    let player = resolve_player(ctx, &player).await?;
+    let config = ctx.data().config.get();
+    let server = config.servers.get(server.as_deref())?;
+
+    let command = format!("kick {player} {}", reason.as_deref().unwrap_or_default());
+    let details = json::json!({ "target": player, "reason": reason });
+    let response = moderate(ctx, server, "kick", command.trim_end().to_owned(), details).await?;
+
+    ctx.send(response_reply(response).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// `lines` joined up, leaving out whatever doesn't fit in an embed description
+fn truncated_lines(lines: &[String]) -> String {
+    let mut text = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        let more = format!("...and {} more", lines.len() - i);
+        if text.chars().count() + line.chars().count() + 1 + more.len() > MAX_DESCRIPTION {
+            text.push_str(&more);
+            break;
+        }
+        text.push_str(line);
+        text.push('\n');
+    }
+    text
+}
+
+#[derive(poise::ChoiceParameter)]
+pub enum BanList {
+    Players,
+    #[name = "IP addresses"]
+    Ips,
+}
+
+/// List who is banned, and when temporary bans run out
+#[poise::command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "config::check_rcon"
+)]
+pub async fn banlist(
+    ctx: Context<'_>,
+    #[description = "Defaults to players"] list: Option<BanList>,
+    #[description = "Defaults to the first server"]
+    #[autocomplete = "server::autocomplete"]
+    server: Option<String>,
+) -> Result<(), Error> {
+    let ip = matches!(list, Some(BanList::Ips));
+    let config = ctx.data().config.get();
+    let server = config.servers.get(server.as_deref())?;
+
+    ctx.defer_ephemeral().await?;
+    let command = if ip { "banlist ips" } else { "banlist players" };
+    let response = run_command(server, command).await?;
+
+    let temp_bans: Vec<String> = ctx
+        .data()
+        .bans
+        .list(&server.id)
+        .await
+        .into_iter()
+        .filter(|ban| ban.ip == ip)
+        .map(|ban| {
+            format!(
+                "`{}` until <t:{}:f> (<t:{}:R>), by {}",
+                ban.target,
+                ban.until,
+                ban.until,
+                ban.moderator.mention()
+            )
+        })
+        .collect();
+    let mut reply = response_reply(response);
+    if !temp_bans.is_empty() {
+        reply = reply.embed(
+            CreateEmbed::new()
+                .title(format!("Temporary bans on {}", server.name))
+                .description(truncated_lines(&temp_bans)),
+        );
+    }
+    ctx.send(reply.ephemeral(true)).await?;
+    Ok(())
+}
@@ -0,0 +1,390 @@
+//! Keeps each server's whitelist and ops in line with Discord roles, going by the accounts
+//! people have linked
+//!
+//! Only players the sync itself put there are ever taken off again, which it remembers in
+//! `role_sync.json` in the server's data directory, so anyone added by hand is left alone.
+
+use std::{collections::BTreeSet, io::ErrorKind, path::Path, sync::Arc};
+
+use futures::StreamExt;
+use poise::serenity_prelude::{GuildId, Http, RoleId, UserId};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{Mutex, Notify},
+    time,
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::{self, Config, ConfigHandle, RoleSync},
+    links::LinkStore,
+    rcon,
+    server::Server,
+    storage, Context, Data, Error,
+};
+
+const FILE_NAME: &str = "role_sync.json";
+/// Embed field values are capped at 1024 characters
+const FIELD_LIMIT: usize = 1024;
+
+/// Who is whitelisted and opped, by lowercase name
+#[derive(Default, PartialEq, Deserialize, Serialize)]
+struct Players {
+    whitelist: BTreeSet<String>,
+    ops: BTreeSet<String>,
+}
+
+/// An entry in `whitelist.json` or `ops.json`
+#[derive(Deserialize)]
+struct Entry {
+    name: String,
+}
+
+async fn read_names(path: &Path) -> Result<BTreeSet<String>, Error> {
+    let bytes = tokio::fs::read(path).await.map_err(|err| {
+        // The server writes both files when it starts, so the path is most likely wrong
+        if err.kind() == ErrorKind::NotFound {
+            format!(
+                "{} doesn't exist, is the server's path right?",
+                path.display()
+            )
+            .into()
+        } else {
+            Error::from(err)
+        }
+    })?;
+    let entries: Vec<Entry> = serde_json::from_slice(&bytes)?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| entry.name.to_lowercase())
+        .collect())
+}
+
+/// Who the server has whitelisted and opped right now
+async fn current(server: &Server) -> Result<Players, Error> {
+    Ok(Players {
+        whitelist: read_names(&server.path.join("whitelist.json")).await?,
+        ops: read_names(&server.path.join("ops.json")).await?,
+    })
+}
+
+/// Who the sync put on the server
+async fn managed(server: &Server) -> Result<Players, Error> {
+    match tokio::fs::read(server.data_path.join(FILE_NAME)).await {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Players::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Who should be whitelisted and opped, going by the roles of everyone who has linked
+async fn wanted(
+    http: &Http,
+    sync: &RoleSync,
+    guild: GuildId,
+    links: &LinkStore,
+) -> Result<Players, Error> {
+    let linked = links.all().await;
+    let mut wanted = Players::default();
+    if linked.is_empty() {
+        return Ok(wanted);
+    }
+
+    // Anyone who left the guild simply isn't listed
+    let mut members = guild.members_iter(http).boxed();
+    while let Some(member) = members.next().await {
+        let member = member?;
+        let Some(name) = linked.get(&member.user.id) else {
+            continue;
+        };
+        let has_role = |roles: &[RoleId]| member.roles.iter().any(|role| roles.contains(role));
+        let name = name.to_lowercase();
+        if has_role(&sync.op_roles) {
+            wanted.ops.insert(name.clone());
+            wanted.whitelist.insert(name);
+        } else if has_role(&sync.whitelist_roles) {
+            wanted.whitelist.insert(name);
+        }
+    }
+    Ok(wanted)
+}
+
+/// What it takes to bring a server in line with the roles
+struct Changes {
+    whitelist_add: Vec<String>,
+    whitelist_remove: Vec<String>,
+    op_add: Vec<String>,
+    op_remove: Vec<String>,
+}
+
+impl Changes {
+    fn between(wanted: &Players, current: &Players, managed: &Players) -> Self {
+        let add = |wanted: &BTreeSet<String>, current: &BTreeSet<String>| {
+            wanted.difference(current).cloned().collect()
+        };
+        let remove =
+            |wanted: &BTreeSet<String>, current: &BTreeSet<String>, managed: &BTreeSet<String>| {
+                managed
+                    .iter()
+                    .filter(|name| current.contains(*name) && !wanted.contains(*name))
+                    .cloned()
+                    .collect()
+            };
+        Self {
+            whitelist_add: add(&wanted.whitelist, &current.whitelist),
+            whitelist_remove: remove(&wanted.whitelist, &current.whitelist, &managed.whitelist),
+            op_add: add(&wanted.ops, &current.ops),
+            op_remove: remove(&wanted.ops, &current.ops, &managed.ops),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.whitelist_add.is_empty()
+            && self.whitelist_remove.is_empty()
+            && self.op_add.is_empty()
+            && self.op_remove.is_empty()
+    }
+
+    /// A field for each list that changes, as a diff
+    fn fields(&self) -> Vec<(&'static str, String, bool)> {
+        [
+            ("Whitelist", &self.whitelist_add, &self.whitelist_remove),
+            ("Ops", &self.op_add, &self.op_remove),
+        ]
+        .into_iter()
+        .filter(|(_, add, remove)| !add.is_empty() || !remove.is_empty())
+        .map(|(name, add, remove)| (name, diff(add, remove), false))
+        .collect()
+    }
+}
+
+/// `add` and `remove` as a diff code block, cut short to fit in a field
+fn diff(add: &[String], remove: &[String]) -> String {
+    let lines: Vec<String> = add
+        .iter()
+        .map(|name| format!("+ {name}"))
+        .chain(remove.iter().map(|name| format!("- {name}")))
+        .collect();
+    let mut text = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        // Leave room for the fences and the line saying how many are left out
+        if text.len() + line.len() > FIELD_LIMIT - 40 {
+            text.push_str(&format!("...and {} more\n", lines.len() - i));
+            break;
+        }
+        text.push_str(line);
+        text.push('\n');
+    }
+    format!("```diff\n{text}```")
+}
+
+/// Run `command` for each of `names`, returning those it failed for
+async fn run_each(server: &Server, command: &str, names: &[String]) -> Vec<String> {
+    let mut failed = Vec::new();
+    for name in names {
+        if let Err(err) = rcon::run_command(server, &format!("{command} {name}")).await {
+            log::warn!("Unable to {command} {name} on {}: {err}", server.id);
+            failed.push(name.clone());
+        }
+    }
+    failed
+}
+
+/// Those of `names` that weren't `failed`
+fn added(names: &[String], failed: &[String]) -> Vec<String> {
+    names
+        .iter()
+        .filter(|name| !failed.contains(name))
+        .cloned()
+        .collect()
+}
+
+/// Work out what has to change on `server`, and change it unless it's a `dry_run`
+async fn sync(server: &Server, wanted: &Players, dry_run: bool) -> Result<Changes, Error> {
+    let old_managed = managed(server).await?;
+    let changes = Changes::between(wanted, &current(server).await?, &old_managed);
+    if dry_run {
+        return Ok(changes);
+    }
+
+    // Anyone who was on the server before the sync stays out of it, while whoever couldn't be
+    // taken off is still the sync's to take off next time
+    let mut managed = Players {
+        whitelist: old_managed
+            .whitelist
+            .intersection(&wanted.whitelist)
+            .cloned()
+            .collect(),
+        ops: old_managed.ops.intersection(&wanted.ops).cloned().collect(),
+    };
+    managed
+        .ops
+        .extend(run_each(server, "deop", &changes.op_remove).await);
+    managed
+        .whitelist
+        .extend(run_each(server, "whitelist remove", &changes.whitelist_remove).await);
+    let failed = run_each(server, "whitelist add", &changes.whitelist_add).await;
+    managed
+        .whitelist
+        .extend(added(&changes.whitelist_add, &failed));
+    let failed = run_each(server, "op", &changes.op_add).await;
+    managed.ops.extend(added(&changes.op_add, &failed));
+
+    if managed != old_managed {
+        storage::write_atomic(
+            &server.data_path.join(FILE_NAME),
+            &serde_json::to_vec(&managed)?,
+        )
+        .await?;
+    }
+    if !changes.is_empty() {
+        log::info!(
+            "Synced roles to {}: whitelisted {:?}, unwhitelisted {:?}, opped {:?}, deopped {:?}",
+            server.id,
+            changes.whitelist_add,
+            changes.whitelist_remove,
+            changes.op_add,
+            changes.op_remove
+        );
+    }
+    Ok(changes)
+}
+
+/// Syncs in the background, and makes sure only one sync runs at a time
+#[derive(Default)]
+pub struct Syncer {
+    wake: Notify,
+    running: Mutex<()>,
+}
+
+impl Syncer {
+    /// Sync soon rather than waiting for the interval
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Sync every server now, then whenever woken or the interval passes, until `token` is
+    /// cancelled
+    pub async fn run(
+        &self,
+        http: Arc<Http>,
+        config: Arc<ConfigHandle>,
+        links: Arc<LinkStore>,
+        token: CancellationToken,
+    ) {
+        loop {
+            let config = config.get();
+            if let Err(err) = self.sync_all(&http, &config, &links).await {
+                log::warn!("Unable to sync roles: {err}");
+            }
+
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = self.wake.notified() => (),
+                _ = time::sleep(config.role_sync.interval()) => (),
+            }
+        }
+    }
+
+    async fn sync_all(&self, http: &Http, config: &Config, links: &LinkStore) -> Result<(), Error> {
+        let sync = &config.role_sync;
+        let Some(guild) = sync.guild() else {
+            return Ok(());
+        };
+        let _running = self.running.lock().await;
+        let wanted = wanted(http, sync, guild, links).await?;
+        for server in sync.servers(&config.servers) {
+            if let Err(err) = self::sync(server, &wanted, false).await {
+                log::warn!("Unable to sync roles to {}: {err}", server.id);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Wake the sync if a linked member of the synced guild joined, left or had their roles changed
+pub async fn on_member_change(data: &Data, guild: GuildId, user: UserId) {
+    if data.config.get().role_sync.guild() == Some(guild) && data.links.name(user).await.is_some() {
+        data.role_sync.wake();
+    }
+}
+
+#[poise::command(
+    slash_command,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "config::check_rcon",
+    subcommands("rolesync::diff", "rolesync::now"),
+    subcommand_required
+)]
+pub async fn rolesync(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+pub mod rolesync {
+    use poise::serenity_prelude::{Color, CreateEmbed};
+
+    use super::{sync, wanted, Changes};
+    use crate::{server, Context, Error};
+
+    /// Show what syncing roles would change, without changing anything
+    #[poise::command(slash_command)]
+    pub async fn diff(
+        ctx: Context<'_>,
+        #[description = "Defaults to the first server"]
+        #[autocomplete = "server::autocomplete"]
+        server: Option<String>,
+    ) -> Result<(), Error> {
+        run(ctx, server.as_deref(), true).await
+    }
+
+    /// Sync roles now rather than waiting for the next sync
+    #[poise::command(slash_command)]
+    pub async fn now(
+        ctx: Context<'_>,
+        #[description = "Defaults to the first server"]
+        #[autocomplete = "server::autocomplete"]
+        server: Option<String>,
+    ) -> Result<(), Error> {
+        run(ctx, server.as_deref(), false).await
+    }
+
+    async fn run(ctx: Context<'_>, server: Option<&str>, dry_run: bool) -> Result<(), Error> {
+        let config = ctx.data().config.get();
+        let sync_config = &config.role_sync;
+        let guild = sync_config.guild().ok_or("Role sync isn't set up")?;
+        let server = config.servers.get(server)?;
+        if !sync_config
+            .servers(&config.servers)
+            .any(|synced| synced.id == server.id)
+        {
+            return Err(format!("{} isn't kept in sync with roles", server.name).into());
+        }
+
+        ctx.defer_ephemeral().await?;
+
+        let changes: Changes = {
+            let _running = ctx.data().role_sync.running.lock().await;
+            let wanted = wanted(
+                &ctx.serenity_context().http,
+                sync_config,
+                guild,
+                &ctx.data().links,
+            )
+            .await?;
+            sync(server, &wanted, dry_run).await?
+        };
+
+        let (title, color) = match (changes.is_empty(), dry_run) {
+            (true, _) => (format!("{} is in sync", server.name), Color::FOOYOO),
+            (false, true) => (format!("Syncing {} would change", server.name), Color::GOLD),
+            (false, false) => (format!("Synced {}", server.name), Color::BLURPLE),
+        };
+        let embed = CreateEmbed::new()
+            .title(title)
+            .fields(changes.fields())
+            .color(color);
+        ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true))
+            .await?;
+        Ok(())
+    }
+}
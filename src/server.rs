@@ -0,0 +1,126 @@
+//! Named profiles for each Minecraft server the bot looks after
+
+use std::{
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use poise::serenity_prelude::AutocompleteChoice;
+
+use crate::{config::ServerConfig, rcon::RconManager, Context, Error};
+
+pub struct Server {
+    pub id: String,
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub bedrock_port: u16,
+    pub query_port: Option<u16>,
+    /// The server's directory
+    pub path: PathBuf,
+    pub log: PathBuf,
+    pub data_path: PathBuf,
+    /// Shared with the server it replaced on a reload, if its RCON settings didn't change
+    pub rcon: Option<Arc<RconManager>>,
+}
+
+impl Server {
+    pub fn from_config(config: ServerConfig, data_path: &Path) -> Self {
+        let rcon = config.rcon.map(|rcon| {
+            Arc::new(RconManager::new(
+                config.host.clone(),
+                rcon.port,
+                rcon.password,
+            ))
+        });
+        Self {
+            name: config.name.unwrap_or_else(|| config.id.clone()),
+            host: config.host,
+            port: config.port,
+            bedrock_port: config.bedrock_port,
+            query_port: config.query_port,
+            log: config
+                .log
+                .unwrap_or_else(|| config.path.join("logs/latest.log")),
+            path: config.path,
+            data_path: config
+                .data_path
+                .unwrap_or_else(|| data_path.join(&config.id)),
+            rcon,
+            id: config.id,
+        }
+    }
+
+    /// The server's RCON connection, or an error saying why there isn't one
+    pub fn rcon(&self) -> Result<&RconManager, Error> {
+        self.rcon.as_deref().ok_or_else(|| {
+            Box::new(io::Error::new(
+                ErrorKind::NotConnected,
+                format!("Rcon is not configured for {}", self.name),
+            )) as _
+        })
+    }
+}
+
+/// Every configured server, in the order they were configured
+pub struct Servers {
+    servers: Vec<Server>,
+}
+
+impl Servers {
+    pub fn new(servers: Vec<Server>) -> Self {
+        Self { servers }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Server> {
+        self.servers.iter()
+    }
+
+    /// Take over the RCON connections of `old` servers whose settings are unchanged, so that
+    /// reloading the configuration doesn't drop them
+    pub fn keep_connections(&mut self, old: &Servers) {
+        for server in &mut self.servers {
+            let Some(rcon) = &server.rcon else {
+                continue;
+            };
+            let kept = old
+                .iter()
+                .filter_map(|old| old.rcon.as_ref())
+                .find(|old| old.same_settings(rcon));
+            if let Some(kept) = kept {
+                server.rcon = Some(kept.clone());
+            }
+        }
+    }
+
+    /// The server with the given id, or the first one if none is given
+    pub fn get(&self, id: Option<&str>) -> Result<&Server, Error> {
+        let server = match id {
+            Some(id) => self.servers.iter().find(|server| server.id == id),
+            None => self.servers.first(),
+        };
+        server.ok_or_else(|| {
+            Box::new(io::Error::new(
+                ErrorKind::NotFound,
+                format!("Unknown server {}", id.unwrap_or_default()),
+            )) as _
+        })
+    }
+}
+
+/// Suggest servers whose id or name contain what's been typed so far
+pub async fn autocomplete(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let partial = partial.to_lowercase();
+    ctx.data()
+        .config
+        .get()
+        .servers
+        .iter()
+        .filter(|server| {
+            server.id.to_lowercase().contains(&partial)
+                || server.name.to_lowercase().contains(&partial)
+        })
+        .map(|server| AutocompleteChoice::new(server.name.clone(), server.id.clone()))
+        .collect()
+}
@@ -0,0 +1,538 @@
+//! Who played when, kept per server in `sessions.json` in the server's data directory
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+use poise::serenity_prelude::{CreateEmbed, Timestamp};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{logs::escape_markdown, server, storage, Context, Error};
+
+const FILE_NAME: &str = "sessions.json";
+const LEADERBOARD_SIZE: usize = 10;
+/// Sessions older than this only count towards totals, which is as far back as any
+/// leaderboard period goes
+const KEEP_SESSIONS: i64 = 31 * 86400;
+/// How often who's still online is saved, which only matters if the bot stops without warning
+const SAVE_SEEN_EVERY: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+struct Session {
+    start: i64,
+    end: i64,
+}
+
+impl Session {
+    /// How much of the session falls after `since`
+    fn length_since(&self, since: i64) -> u64 {
+        (self.end - self.start.max(since)).max(0) as u64
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct Player {
+    /// As the player last spelled it
+    name: String,
+    first_seen: i64,
+    last_seen: i64,
+    /// Seconds played in finished sessions
+    total: u64,
+    longest: u64,
+    online_since: Option<i64>,
+    #[serde(default)]
+    sessions: Vec<Session>,
+}
+
+impl Player {
+    fn new(name: &str, now: i64) -> Self {
+        Self {
+            name: name.to_owned(),
+            first_seen: now,
+            last_seen: now,
+            total: 0,
+            longest: 0,
+            online_since: None,
+            sessions: Vec::new(),
+        }
+    }
+
+    /// Finish the current session at `end`, returning how long it was
+    fn close(&mut self, end: i64) -> Option<Duration> {
+        let start = self.online_since.take()?;
+        let session = Session {
+            start,
+            end: end.max(start),
+        };
+        let length = session.length_since(start);
+        self.total += length;
+        self.longest = self.longest.max(length);
+        self.last_seen = session.end;
+        self.sessions.push(session);
+        self.sessions
+            .retain(|session| session.end > end - KEEP_SESSIONS);
+        Some(Duration::from_secs(length))
+    }
+
+    /// Seconds played in all, counting the current session up to `now`
+    fn played(&self, now: i64) -> u64 {
+        let current = self
+            .online_since
+            .map_or(0, |start| (now - start).max(0) as u64);
+        self.total + current
+    }
+
+    /// Seconds played since `since`, counting the current session up to `now`
+    ///
+    /// Only goes back [`KEEP_SESSIONS`].
+    fn played_since(&self, since: i64, now: i64) -> u64 {
+        let current = self.online_since.map(|start| Session { start, end: now });
+        self.sessions
+            .iter()
+            .chain(&current)
+            .map(|session| session.length_since(since))
+            .sum()
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct Players {
+    /// By lowercase name, since names are case insensitive
+    players: BTreeMap<String, Player>,
+}
+
+impl Players {
+    fn player(&mut self, name: &str, now: i64) -> &mut Player {
+        let player = self
+            .players
+            .entry(name.to_lowercase())
+            .or_insert_with(|| Player::new(name, now));
+        player.name = name.to_owned();
+        player
+    }
+
+    fn get(&self, name: &str) -> Option<&Player> {
+        self.players.get(&name.to_lowercase())
+    }
+}
+
+/// Every server's sessions, loaded as they're needed
+#[derive(Default)]
+pub struct SessionStore {
+    servers: Mutex<HashMap<PathBuf, Players>>,
+    /// When each server's players were last saved for being seen
+    seen_saved: std::sync::Mutex<HashMap<PathBuf, Instant>>,
+}
+
+impl SessionStore {
+    async fn read<T>(&self, data_path: &Path, f: impl FnOnce(&Players) -> T) -> Result<T, Error> {
+        let mut servers = self.servers.lock().await;
+        let players = cached(&mut servers, data_path).await?;
+        Ok(f(players))
+    }
+
+    async fn update<T>(
+        &self,
+        data_path: &Path,
+        f: impl FnOnce(&mut Players) -> T,
+    ) -> Result<T, Error> {
+        let mut servers = self.servers.lock().await;
+        let players = cached(&mut servers, data_path).await?;
+        let result = f(players);
+        save(data_path, players).await?;
+        Ok(result)
+    }
+
+    /// Update when players were last seen, only saving now and then rather than on every poll
+    async fn mark_seen(
+        &self,
+        data_path: &Path,
+        f: impl FnOnce(&mut Players, i64),
+    ) -> Result<(), Error> {
+        let mut servers = self.servers.lock().await;
+        let players = cached(&mut servers, data_path).await?;
+        f(players, Timestamp::now().unix_timestamp());
+
+        let due = {
+            let mut saved = self.seen_saved.lock().unwrap();
+            let last = saved
+                .entry(data_path.to_owned())
+                .or_insert_with(Instant::now);
+            let due = last.elapsed() >= SAVE_SEEN_EVERY;
+            if due {
+                *last = Instant::now();
+            }
+            due
+        };
+        if due {
+            save(data_path, players).await?;
+        }
+        Ok(())
+    }
+
+    /// Start a session for `name`, unless one is already going
+    pub async fn join(&self, data_path: &Path, name: &str) -> Result<(), Error> {
+        let now = Timestamp::now().unix_timestamp();
+        self.update(data_path, |players| {
+            let player = players.player(name, now);
+            player.last_seen = now;
+            player.online_since.get_or_insert(now);
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Finish `name`'s session, returning how long it was if it was known to have started
+    pub async fn leave(&self, data_path: &Path, name: &str) -> Result<Option<Duration>, Error> {
+        let now = Timestamp::now().unix_timestamp();
+        self.update(data_path, |players| players.player(name, now).close(now))
+            .await
+    }
+
+    /// Note that `names` are still online, without starting sessions for them
+    pub async fn seen(&self, data_path: &Path, names: &[String]) -> Result<(), Error> {
+        self.mark_seen(data_path, |players, now| {
+            for name in names {
+                if let Some(player) = players.players.get_mut(&name.to_lowercase()) {
+                    player.last_seen = now;
+                }
+            }
+        })
+        .await
+    }
+
+    /// Note that everyone with a session going is still online
+    pub async fn seen_online(&self, data_path: &Path) -> Result<(), Error> {
+        self.mark_seen(data_path, |players, now| {
+            for player in players.players.values_mut() {
+                if player.online_since.is_some() {
+                    player.last_seen = now;
+                }
+            }
+        })
+        .await
+    }
+
+    /// Finish every session going, as of when each player was last seen
+    ///
+    /// For when it's unknown who left while nobody was watching.
+    pub async fn close_all(&self, data_path: &Path) -> Result<(), Error> {
+        self.update(data_path, |players| {
+            for player in players.players.values_mut() {
+                player.close(player.last_seen);
+            }
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn names(&self, data_path: &Path) -> Result<Vec<String>, Error> {
+        self.read(data_path, |players| {
+            players
+                .players
+                .values()
+                .map(|player| player.name.clone())
+                .collect()
+        })
+        .await
+    }
+}
+
+async fn cached<'a>(
+    servers: &'a mut HashMap<PathBuf, Players>,
+    data_path: &Path,
+) -> Result<&'a mut Players, Error> {
+    if !servers.contains_key(data_path) {
+        let players = load(data_path).await?;
+        servers.insert(data_path.to_owned(), players);
+    }
+    Ok(servers.get_mut(data_path).unwrap())
+}
+
+async fn save(data_path: &Path, players: &Players) -> Result<(), Error> {
+    let data = serde_json::to_vec(players)?;
+    storage::write_atomic(&data_path.join(FILE_NAME), &data).await
+}
+
+async fn load(data_path: &Path) -> Result<Players, Error> {
+    match tokio::fs::read(data_path.join(FILE_NAME)).await {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Players::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Suggest players any server has seen
+async fn autocomplete_player(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let partial = partial.to_lowercase();
+    let config = ctx.data().config.get();
+    let mut names = Vec::new();
+    for server in config.servers.iter() {
+        if let Ok(server_names) = ctx.data().sessions.names(&server.data_path).await {
+            names.extend(server_names);
+        }
+    }
+    names
+        .into_iter()
+        .filter(|name| name.to_lowercase().starts_with(&partial))
+        .sorted_by_key(|name| name.to_lowercase())
+        .dedup()
+        .take(25)
+        .collect()
+}
+
+/// A rough duration like `2d 3h` or `12m`, only showing the two largest units
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let units = [
+        (secs / 86400, "d"),
+        (secs / 3600 % 24, "h"),
+        (secs / 60 % 60, "m"),
+        (secs % 60, "s"),
+    ];
+    let parts: Vec<String> = units
+        .iter()
+        .skip_while(|(value, _)| *value == 0)
+        .take(2)
+        .filter(|(value, _)| *value > 0)
+        .map(|(value, unit)| format!("{value}{unit}"))
+        .collect();
+    if parts.is_empty() {
+        "0s".into()
+    } else {
+        parts.join(" ")
+    }
+}
+
+/// `player`, or the name the author has linked if they didn't give one
+async fn player_or_linked(ctx: Context<'_>, player: Option<String>) -> Result<String, Error> {
+    match player {
+        Some(player) => Ok(player),
+        None => ctx
+            .data()
+            .links
+            .name(ctx.author().id)
+            .await
+            .ok_or_else(|| "Give a player, or link your account with /link".into()),
+    }
+}
+
+fn unknown_player(player: &str) -> Error {
+    format!("{player} has never been seen").into()
+}
+
+/// When a player was last online
+#[poise::command(slash_command)]
+pub async fn seen(
+    ctx: Context<'_>,
+    #[description = "Defaults to the account you've linked"]
+    #[autocomplete = "autocomplete_player"]
+    player: Option<String>,
+    #[description = "Defaults to the first server"]
+    #[autocomplete = "server::autocomplete"]
+    server: Option<String>,
+) -> Result<(), Error> {
+    let player = player_or_linked(ctx, player).await?;
+    let config = ctx.data().config.get();
+    let server = config.servers.get(server.as_deref())?;
+    let description = ctx
+        .data()
+        .sessions
+        .read(&server.data_path, |players| {
+            let player = players.get(&player)?;
+            let name = escape_markdown(&player.name);
+            let status = match player.online_since {
+                Some(since) => format!("**{name}** has been online since <t:{since}:R>"),
+                None => format!("**{name}** was last seen <t:{}:R>", player.last_seen),
+            };
+            Some(format!("{status}\nFirst seen <t:{}:D>", player.first_seen))
+        })
+        .await?;
+    let description = description.ok_or_else(|| unknown_player(&player))?;
+
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::new()
+                .title(&server.name)
+                .description(description),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// How long a player has played
+#[poise::command(slash_command)]
+pub async fn playtime(
+    ctx: Context<'_>,
+    #[description = "Defaults to the account you've linked"]
+    #[autocomplete = "autocomplete_player"]
+    player: Option<String>,
+    #[description = "Defaults to the first server"]
+    #[autocomplete = "server::autocomplete"]
+    server: Option<String>,
+) -> Result<(), Error> {
+    let player = player_or_linked(ctx, player).await?;
+    let config = ctx.data().config.get();
+    let server = config.servers.get(server.as_deref())?;
+    let now = Timestamp::now().unix_timestamp();
+    let embed = ctx
+        .data()
+        .sessions
+        .read(&server.data_path, |players| {
+            let player = players.get(&player)?;
+            let total = Duration::from_secs(player.played(now));
+            let current = player.online_since.map(|since| (now - since).max(0) as u64);
+            let longest = Duration::from_secs(player.longest.max(current.unwrap_or(0)));
+            let sessions = player.sessions.len() + usize::from(current.is_some());
+            Some(
+                CreateEmbed::new()
+                    .title(format!("{} on {}", player.name, server.name))
+                    .fields([
+                        ("Playtime", format_duration(total), true),
+                        ("Longest Session", format_duration(longest), true),
+                        ("Sessions", sessions.to_string(), true),
+                        ("First Seen", format!("<t:{}:D>", player.first_seen), true),
+                    ]),
+            )
+        })
+        .await?;
+    let embed = embed.ok_or_else(|| unknown_player(&player))?;
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+#[derive(poise::ChoiceParameter)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    #[name = "All time"]
+    All,
+}
+
+impl Period {
+    fn seconds(&self) -> Option<i64> {
+        match self {
+            Self::Day => Some(86400),
+            Self::Week => Some(7 * 86400),
+            Self::Month => Some(30 * 86400),
+            Self::All => None,
+        }
+    }
+}
+
+#[poise::command(
+    slash_command,
+    subcommands("leaderboard::playtime"),
+    subcommand_required
+)]
+pub async fn leaderboard(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+pub mod leaderboard {
+    use itertools::Itertools;
+    use poise::serenity_prelude::{CreateEmbed, Mentionable, Timestamp};
+    use tokio::time::Duration;
+
+    use poise::ChoiceParameter;
+
+    use super::{format_duration, Period, LEADERBOARD_SIZE};
+    use crate::{logs::escape_markdown, server, Context, Error};
+
+    /// Who has played the most
+    #[poise::command(slash_command)]
+    pub async fn playtime(
+        ctx: Context<'_>,
+        #[description = "Defaults to all time"] period: Option<Period>,
+        #[description = "Defaults to the first server"]
+        #[autocomplete = "server::autocomplete"]
+        server: Option<String>,
+    ) -> Result<(), Error> {
+        let period = period.unwrap_or(Period::All);
+        let config = ctx.data().config.get();
+        let server = config.servers.get(server.as_deref())?;
+        let now = Timestamp::now().unix_timestamp();
+        let since = period.seconds().map(|seconds| now - seconds);
+
+        let leaders = ctx
+            .data()
+            .sessions
+            .read(&server.data_path, |players| {
+                players
+                    .players
+                    .values()
+                    .map(|player| {
+                        let played = match since {
+                            Some(since) => player.played_since(since, now),
+                            None => player.played(now),
+                        };
+                        (player.name.clone(), played)
+                    })
+                    .filter(|(_, played)| *played > 0)
+                    .sorted_by(|a, b| b.1.cmp(&a.1))
+                    .take(LEADERBOARD_SIZE)
+                    .collect_vec()
+            })
+            .await?;
+
+        let users = ctx.data().links.users().await;
+        let description = if leaders.is_empty() {
+            "Nobody has played yet".to_string()
+        } else {
+            leaders
+                .iter()
+                .enumerate()
+                .map(|(i, (name, played))| {
+                    let user = users
+                        .get(&name.to_lowercase())
+                        .map_or_else(String::new, |user| format!(" ({})", user.mention()));
+                    format!(
+                        "{}. **{}**{user} {}",
+                        i + 1,
+                        escape_markdown(name),
+                        format_duration(Duration::from_secs(*played))
+                    )
+                })
+                .join("\n")
+        };
+
+        ctx.send(
+            poise::CreateReply::default().embed(
+                CreateEmbed::new()
+                    .title(format!("Playtime on {} ({})", server.name, period.name()))
+                    .description(description),
+            ),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Player, KEEP_SESSIONS};
+
+    #[test]
+    fn old_sessions_only_count_towards_totals() {
+        let mut player = Player::new("Steve", 0);
+        player.online_since = Some(0);
+        player.close(3600);
+        let later = 3600 + KEEP_SESSIONS + 60;
+        player.online_since = Some(later);
+        player.close(later + 60);
+
+        assert_eq!(player.sessions.len(), 1);
+        assert_eq!(player.played(later + 60), 3660);
+        assert_eq!(player.played_since(later - 86400, later + 60), 60);
+    }
+}
@@ -0,0 +1,358 @@
+//! Client for the Java Edition Server List Ping protocol
+//!
+//! Servers from before 1.7 don't understand the modern handshake, so [`status`] falls back to
+//! the 1.4–1.6 and beta era pings when it fails.
+
+use std::{
+    io::{self, ErrorKind},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, BufStream},
+    net::TcpStream,
+    time::{self, Duration, Instant},
+};
+
+use crate::Error;
+
+const PROTOCOL_VERSION: i32 = 763;
+const HANDSHAKE: i32 = 0x00;
+const STATUS_REQUEST: i32 = 0x00;
+const PING: i32 = 0x01;
+const NEXT_STATE_STATUS: i32 = 1;
+const LEGACY_PING: u8 = 0xFE;
+const LEGACY_KICK: u8 = 0xFF;
+const PLUGIN_MESSAGE: u8 = 0xFA;
+// 1.6.4, servers before 1.6 ignore everything after the first two bytes anyway
+const LEGACY_PROTOCOL_VERSION: u8 = 78;
+// A status response is at most a 32767 character string, which is 4 bytes per character
+const MAX_PACKET: usize = 1 << 17;
+
+/// How long to wait on a server before considering it unreachable
+#[derive(Clone, Copy)]
+pub struct Timeouts {
+    pub connect: Duration,
+    /// Applies to each read and write separately
+    pub io: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(5),
+            io: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StatusResponse {
+    pub version: Version,
+    pub players: Option<Players>,
+    /// A text component
+    #[serde(default)]
+    pub description: Value,
+    /// A `data:image/png;base64,` URI
+    pub favicon: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Version {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct Players {
+    pub max: u64,
+    pub online: u64,
+    /// Some of the players online, often capped at 12 and sometimes not players at all
+    #[serde(default)]
+    pub sample: Vec<PlayerSample>,
+}
+
+#[derive(Deserialize)]
+pub struct PlayerSample {
+    pub name: String,
+}
+
+pub struct ServerStatus {
+    pub response: StatusResponse,
+    /// Round trip time of a ping, if the server answered one
+    pub latency: Option<Duration>,
+}
+
+/// Request the status of a server, falling back to legacy pings for old servers
+pub async fn status(host: &str, port: u16, timeouts: Timeouts) -> Result<ServerStatus, Error> {
+    // If the server can't even be reached there's nothing to fall back to
+    let stream = connect(host, port, timeouts).await?;
+    let err = match modern(stream, host, port, timeouts).await {
+        Ok(status) => return Ok(status),
+        Err(err) => err,
+    };
+    log::debug!("Modern ping of {host}:{port} failed ({err}), trying legacy pings");
+
+    match legacy(host, port, timeouts, true).await {
+        Ok(status) => Ok(status),
+        Err(err) => {
+            log::debug!("1.4 ping of {host}:{port} failed ({err}), trying beta ping");
+            legacy(host, port, timeouts, false).await
+        }
+    }
+}
+
+/// The 1.7+ handshake and status request, then measure latency with a ping
+async fn modern(
+    stream: TcpStream,
+    host: &str,
+    port: u16,
+    timeouts: Timeouts,
+) -> Result<ServerStatus, Error> {
+    let mut conn = Connection {
+        stream: BufStream::new(stream),
+        timeout: timeouts.io,
+    };
+
+    let mut handshake = Vec::with_capacity(host.len() + 16);
+    put_varint(&mut handshake, PROTOCOL_VERSION);
+    put_string(&mut handshake, host);
+    handshake.extend_from_slice(&port.to_be_bytes());
+    put_varint(&mut handshake, NEXT_STATE_STATUS);
+    conn.write_packet(HANDSHAKE, &handshake).await?;
+    conn.write_packet(STATUS_REQUEST, &[]).await?;
+
+    let (id, response) = conn.read_packet().await?;
+    if id != STATUS_REQUEST {
+        return Err(invalid(format!(
+            "Expected a status response, got packet {id}"
+        )));
+    }
+    let mut response = response.as_slice();
+    let json = get_string(&mut response)?;
+    let response: StatusResponse = serde_json::from_str(json)?;
+
+    // Plenty of servers and proxies don't bother answering pings, which is fine
+    let latency = match conn.ping().await {
+        Ok(latency) => Some(latency),
+        Err(err) => {
+            log::debug!("{host}:{port} did not answer ping: {err}");
+            None
+        }
+    };
+
+    Ok(ServerStatus { response, latency })
+}
+
+/// The 1.4–1.6 ping if `plugin_message` is set, otherwise the beta one
+async fn legacy(
+    host: &str,
+    port: u16,
+    timeouts: Timeouts,
+    plugin_message: bool,
+) -> Result<ServerStatus, Error> {
+    let mut stream = connect(host, port, timeouts).await?;
+
+    let mut request = vec![LEGACY_PING];
+    if plugin_message {
+        // 0x01 asks for the 1.4 response format, and the MC|PingHost plugin message
+        // is what 1.6 clients send so the server knows who was asked for
+        request.extend_from_slice(&[0x01, PLUGIN_MESSAGE]);
+        put_utf16(&mut request, "MC|PingHost");
+        let mut data = vec![LEGACY_PROTOCOL_VERSION];
+        put_utf16(&mut data, host);
+        data.extend_from_slice(&i32::from(port).to_be_bytes());
+        request.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        request.extend_from_slice(&data);
+    }
+
+    let start = Instant::now();
+    let response = timeout(timeouts.io, async {
+        stream.write_all(&request).await?;
+
+        let id = stream.read_u8().await?;
+        if id != LEGACY_KICK {
+            return Err(invalid(format!("Expected a kick packet, got {id:#04x}")));
+        }
+        let len = stream.read_u16().await? as usize;
+        let mut units = vec![0; len * 2];
+        stream.read_exact(&mut units).await?;
+        let units: Vec<u16> = units
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+            .collect();
+        Ok(String::from_utf16_lossy(&units))
+    })
+    .await??;
+    let latency = start.elapsed();
+
+    let (motd, version, online, max) = if let Some(fields) = response.strip_prefix("§1\0") {
+        // protocol, version, motd, online, max
+        let fields: Vec<&str> = fields.split('\0').collect();
+        match fields[..] {
+            [_, version, motd, online, max] => (motd, version, online, max),
+            _ => return Err(invalid("Malformed legacy ping response")),
+        }
+    } else {
+        // motd§online§max, and the motd can't contain § for exactly that reason
+        let mut fields = response.rsplitn(3, '§');
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some(max), Some(online), Some(motd)) => (motd, "Beta 1.8 - 1.3", online, max),
+            _ => return Err(invalid("Malformed beta ping response")),
+        }
+    };
+
+    let response = StatusResponse {
+        version: Version {
+            name: version.to_owned(),
+        },
+        players: Some(Players {
+            online: online.parse().unwrap_or(0),
+            max: max.parse().unwrap_or(0),
+            sample: Vec::new(),
+        }),
+        description: Value::String(motd.to_owned()),
+        favicon: None,
+    };
+
+    Ok(ServerStatus {
+        response,
+        latency: Some(latency),
+    })
+}
+
+async fn connect(host: &str, port: u16, timeouts: Timeouts) -> io::Result<TcpStream> {
+    timeout(timeouts.connect, TcpStream::connect((host, port))).await?
+}
+
+struct Connection {
+    stream: BufStream<TcpStream>,
+    timeout: Duration,
+}
+
+impl Connection {
+    async fn write_packet(&mut self, id: i32, data: &[u8]) -> Result<(), Error> {
+        let mut body = Vec::with_capacity(data.len() + 5);
+        put_varint(&mut body, id);
+        body.extend_from_slice(data);
+
+        let mut packet = Vec::with_capacity(body.len() + 5);
+        put_varint(&mut packet, body.len() as i32);
+        packet.extend_from_slice(&body);
+
+        timeout(self.timeout, async {
+            self.stream.write_all(&packet).await?;
+            self.stream.flush().await
+        })
+        .await??;
+        Ok(())
+    }
+
+    async fn read_packet(&mut self) -> Result<(i32, Vec<u8>), Error> {
+        timeout(self.timeout, async {
+            let len = self.read_varint().await?;
+            let len = usize::try_from(len)
+                .ok()
+                .filter(|len| (1..=MAX_PACKET).contains(len))
+                .ok_or_else(|| invalid(format!("Invalid packet length {len}")))?;
+
+            let mut packet = vec![0; len];
+            self.stream.read_exact(&mut packet).await?;
+
+            let mut body = packet.as_slice();
+            let id = get_varint(&mut body)?;
+            Ok((id, body.to_vec()))
+        })
+        .await?
+    }
+
+    async fn read_varint(&mut self) -> Result<i32, Error> {
+        let mut value = 0;
+        for i in 0..5 {
+            let byte = self.stream.read_u8().await?;
+            value |= ((byte & 0x7F) as i32) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("VarInt is too long"))
+    }
+
+    async fn ping(&mut self) -> Result<Duration, Error> {
+        let payload = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis() as i64);
+        let start = Instant::now();
+        self.write_packet(PING, &payload.to_be_bytes()).await?;
+        let (id, pong) = self.read_packet().await?;
+        let latency = start.elapsed();
+
+        if id != PING || pong != payload.to_be_bytes() {
+            return Err(invalid("Pong does not match ping"));
+        }
+        Ok(latency)
+    }
+}
+
+async fn timeout<F: std::future::Future>(duration: Duration, future: F) -> io::Result<F::Output> {
+    time::timeout(duration, future)
+        .await
+        .map_err(|_| io::Error::new(ErrorKind::TimedOut, "Server took too long to respond"))
+}
+
+fn invalid(msg: impl Into<String>) -> Error {
+    Box::new(io::Error::new(ErrorKind::InvalidData, msg.into()))
+}
+
+fn put_varint(buf: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7F == 0 {
+            buf.push(value as u8);
+            return;
+        }
+        buf.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+fn get_varint(buf: &mut &[u8]) -> Result<i32, Error> {
+    let mut value = 0;
+    for i in 0..5 {
+        let (&byte, rest) = buf
+            .split_first()
+            .ok_or_else(|| invalid("Truncated VarInt"))?;
+        *buf = rest;
+        value |= ((byte & 0x7F) as i32) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("VarInt is too long"))
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    put_varint(buf, s.len() as i32);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// A string as sent by pre-netty versions, UTF-16 with a length prefix in code units
+fn put_utf16(buf: &mut Vec<u8>, s: &str) {
+    let units: Vec<u16> = s.encode_utf16().collect();
+    buf.extend_from_slice(&(units.len() as u16).to_be_bytes());
+    for unit in units {
+        buf.extend_from_slice(&unit.to_be_bytes());
+    }
+}
+
+fn get_string<'a>(buf: &mut &'a [u8]) -> Result<&'a str, Error> {
+    let len = get_varint(buf)?;
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|len| *len <= buf.len())
+        .ok_or_else(|| invalid("String is longer than its packet"))?;
+    let (s, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(std::str::from_utf8(s)?)
+}
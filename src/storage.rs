@@ -0,0 +1,85 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use tokio::{
+    fs::{self, OpenOptions},
+    io::AsyncWriteExt,
+};
+
+use crate::Error;
+
+/// Data directories being recorded to, by what is recording
+static CLAIMED: Mutex<BTreeSet<(&str, PathBuf)>> = Mutex::new(BTreeSet::new());
+
+/// The right to record `what` to a server's data directory, held by one monitor at a time so
+/// nothing is recorded twice
+pub struct Claim {
+    what: &'static str,
+    data_path: PathBuf,
+}
+
+impl Claim {
+    pub fn data_path(&self) -> &Path {
+        &self.data_path
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        let key = (self.what, std::mem::take(&mut self.data_path));
+        CLAIMED.lock().unwrap().remove(&key);
+    }
+}
+
+/// Claim `data_path` for recording `what`, unless another monitor already has
+pub fn claim(what: &'static str, data_path: &Path) -> Option<Claim> {
+    CLAIMED
+        .lock()
+        .unwrap()
+        .insert((what, data_path.to_owned()))
+        .then(|| Claim {
+            what,
+            data_path: data_path.to_owned(),
+        })
+}
+
+/// Replace the contents of `path` without ever leaving a partially written file behind
+///
+/// The data is written and synced to a sibling temporary file which is then renamed over
+/// `path`, so a crash at any point leaves either the old or the new contents.
+pub async fn write_atomic(path: &Path, data: &[u8]) -> Result<(), Error> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let mut file = fs::File::create(&tmp).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    fs::rename(&tmp, path).await?;
+
+    // Make sure the rename itself survives a crash
+    if let Some(dir) = path.parent() {
+        if let Ok(dir) = fs::File::open(dir).await {
+            let _ = dir.sync_all().await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Add a line to the end of `path`, creating it if needed
+///
+/// A line torn by a crash is left behind, so readers have to skip lines they can't parse.
+pub async fn append_line(path: &Path, line: &str) -> Result<(), Error> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(format!("{line}\n").as_bytes()).await?;
+    Ok(())
+}
@@ -0,0 +1,209 @@
+//! Outages seen by the status monitor, kept per server in `incidents.json` in the server's data
+//! directory
+
+use std::{io::ErrorKind, path::Path};
+
+use poise::serenity_prelude::{CreateEmbed, Timestamp};
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
+
+use crate::{server, sessions::format_duration, storage, Context, Error};
+
+const FILE_NAME: &str = "incidents.json";
+const RECENT_INCIDENTS: usize = 5;
+const PERIODS: [(&str, i64); 3] = [("24h", 86400), ("7d", 7 * 86400), ("30d", 30 * 86400)];
+/// How often a status monitor notes that it's still watching, in seconds
+pub const HEARTBEAT: i64 = 5 * 60;
+
+/// How long after the last heartbeat nothing can be said about the server any more
+fn stale_after(interval: i64) -> i64 {
+    HEARTBEAT + 2 * interval
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+struct Incident {
+    start: i64,
+    /// Nothing while the server is still down
+    end: Option<i64>,
+}
+
+impl Incident {
+    /// Seconds of the incident between `from` and `to`, counting an ongoing one up to `to`
+    fn overlap(&self, from: i64, to: i64) -> i64 {
+        (self.end.unwrap_or(to).min(to) - self.start.max(from)).max(0)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct Incidents {
+    /// When monitoring started, since there's no saying how available the server was before
+    since: i64,
+    incidents: Vec<Incident>,
+    /// When the server was last known to be watched
+    #[serde(default)]
+    checked: Option<i64>,
+    /// Whenever nothing was watching, which counts as neither up nor down
+    #[serde(default)]
+    gaps: Vec<Incident>,
+}
+
+impl Incidents {
+    fn new(since: i64) -> Self {
+        Self {
+            since,
+            incidents: Vec::new(),
+            checked: Some(since),
+            gaps: Vec::new(),
+        }
+    }
+
+    fn ongoing(&mut self) -> Option<&mut Incident> {
+        self.incidents
+            .last_mut()
+            .filter(|incident| incident.end.is_none())
+    }
+}
+
+async fn load(data_path: &Path) -> Result<Option<Incidents>, Error> {
+    match tokio::fs::read(data_path.join(FILE_NAME)).await {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn save(data_path: &Path, incidents: &Incidents) -> Result<(), Error> {
+    let data = serde_json::to_vec(incidents)?;
+    storage::write_atomic(&data_path.join(FILE_NAME), &data).await
+}
+
+/// Start keeping track of incidents, returning when the ongoing one started if there is one
+///
+/// If nothing was watching for a while, that time is set aside and any ongoing incident is
+/// taken to have ended when watching stopped.
+pub async fn track(data_path: &Path, interval: i64) -> Result<Option<i64>, Error> {
+    let now = Timestamp::now().unix_timestamp();
+    let mut incidents = load(data_path).await?.unwrap_or(Incidents::new(now));
+    if let Some(checked) = incidents
+        .checked
+        .filter(|checked| now - checked > stale_after(interval))
+    {
+        incidents.gaps.push(Incident {
+            start: checked,
+            end: Some(now),
+        });
+        if let Some(incident) = incidents.ongoing() {
+            incident.end = Some(checked.max(incident.start));
+        }
+    }
+    incidents.checked = Some(now);
+    save(data_path, &incidents).await?;
+    Ok(incidents.ongoing().map(|incident| incident.start))
+}
+
+/// Note that the server is still being watched
+pub async fn heartbeat(data_path: &Path, time: i64) -> Result<(), Error> {
+    let mut incidents = load(data_path).await?.unwrap_or(Incidents::new(time));
+    incidents.checked = Some(time);
+    save(data_path, &incidents).await
+}
+
+pub async fn start(data_path: &Path, since: i64, time: i64) -> Result<(), Error> {
+    let mut incidents = load(data_path).await?.unwrap_or(Incidents::new(since));
+    incidents.checked = Some(time);
+    if incidents.ongoing().is_none() {
+        incidents.incidents.push(Incident {
+            start: since,
+            end: None,
+        });
+    }
+    save(data_path, &incidents).await
+}
+
+pub async fn end(data_path: &Path, time: i64) -> Result<(), Error> {
+    let Some(mut incidents) = load(data_path).await? else {
+        return Ok(());
+    };
+    incidents.checked = Some(time);
+    if let Some(incident) = incidents.ongoing() {
+        incident.end = Some(time);
+    }
+    save(data_path, &incidents).await
+}
+
+/// How available the server has been, and its latest outages
+#[poise::command(slash_command)]
+pub async fn uptime(
+    ctx: Context<'_>,
+    #[description = "Defaults to the first server"]
+    #[autocomplete = "server::autocomplete"]
+    server: Option<String>,
+) -> Result<(), Error> {
+    let config = ctx.data().config.get();
+    let server = config.servers.get(server.as_deref())?;
+    let incidents = load(&server.data_path).await?.ok_or_else(|| {
+        format!(
+            "{} isn't being tracked, start a status monitor for it first",
+            server.name
+        )
+    })?;
+    let now = Timestamp::now().unix_timestamp();
+    // Whatever happened since the status monitor stopped is anyone's guess
+    let interval = config.monitors.status_interval().as_secs() as i64;
+    let watched_until = incidents
+        .checked
+        .filter(|checked| now - checked > stale_after(interval))
+        .unwrap_or(now);
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("Uptime of {}", server.name))
+        .description(format!("Tracked since <t:{}:D>", incidents.since));
+    for (name, seconds) in PERIODS {
+        let from = (now - seconds).max(incidents.since);
+        let unwatched: i64 = incidents
+            .gaps
+            .iter()
+            .map(|gap| gap.overlap(from, watched_until))
+            .sum();
+        let tracked = watched_until - from - unwatched;
+        let down: i64 = incidents
+            .incidents
+            .iter()
+            .map(|incident| incident.overlap(from, watched_until))
+            .sum();
+        let value = if tracked > 0 {
+            let percent = 100.0 * (tracked - down.min(tracked)) as f64 / tracked as f64;
+            format!("{percent:.2}%")
+        } else {
+            "-".to_string()
+        };
+        embed = embed.field(name, value, true);
+    }
+
+    let recent: Vec<String> = incidents
+        .incidents
+        .iter()
+        .rev()
+        .take(RECENT_INCIDENTS)
+        .map(|incident| {
+            let length = Duration::from_secs(incident.overlap(incident.start, now) as u64);
+            match incident.end {
+                Some(_) => format!("<t:{}:f> for {}", incident.start, format_duration(length)),
+                None => format!(
+                    "<t:{}:f>, ongoing for {}",
+                    incident.start,
+                    format_duration(length)
+                ),
+            }
+        })
+        .collect();
+    let recent = if recent.is_empty() {
+        "None".to_string()
+    } else {
+        recent.join("\n")
+    };
+    embed = embed.field("Recent Outages", recent, false);
+
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
@@ -0,0 +1,36 @@
+//! Small helpers shared by commands that have nothing else in common
+
+use crate::Error;
+
+/// Seconds in a duration like `90m`, `36h`, `7d` or `2w`
+pub fn parse_duration(text: &str) -> Result<i64, Error> {
+    let text = text.trim();
+    let invalid = || format!("Invalid duration {text:?}, try something like 24h, 7d or 2w");
+    let unit = match text.chars().last() {
+        Some('m') => 60,
+        Some('h') => 3600,
+        Some('d') => 86400,
+        Some('w') => 7 * 86400,
+        _ => return Err(invalid().into()),
+    };
+    let amount: i64 = text[..text.len() - 1].parse().map_err(|_| invalid())?;
+    if amount <= 0 {
+        return Err(invalid().into());
+    }
+    amount.checked_mul(unit).ok_or_else(|| invalid().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("90m").unwrap(), 90 * 60);
+        assert_eq!(parse_duration(" 2w ").unwrap(), 14 * 86400);
+        assert!(parse_duration("0d").is_err());
+        assert!(parse_duration("7").is_err());
+        assert!(parse_duration("é").is_err());
+        assert!(parse_duration(&format!("{}w", i64::MAX)).is_err());
+    }
+}